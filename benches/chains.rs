@@ -0,0 +1,164 @@
+use std::{fmt::Write, hint::black_box};
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use extract_chains::{
+    Anchor, Chain, Read,
+    filter::ReadFilter,
+    input::{open_input, read_input},
+    log::Verbosity,
+    parse::parse_file,
+    plot::{PlotOptions, plot_chain, render_chain_rgba},
+    stream::parse_reader,
+};
+
+const READ_LEN: u32 = 1000;
+const K: u32 = 15;
+
+/// A dump of `reads` reads, each with one chain of `anchors` anchors on a
+/// match-only path.
+fn synthetic_dump(reads: usize, anchors: u32) -> String {
+    let anchor_list = |ref_start: u32| {
+        (0..anchors).fold(String::new(), |mut list, idx| {
+            let query_pos = idx * READ_LEN / anchors;
+            write!(list, "{{{},{query_pos}}}", ref_start + query_pos).unwrap();
+            list
+        })
+    };
+
+    let mut dump = String::new();
+    for read in 0..reads {
+        let ref_start = 10_000 + read as u32 * 2 * READ_LEN;
+        let anchors = anchor_list(ref_start);
+        writeln!(dump, "Query: read{read}").unwrap();
+        writeln!(dump, "L={READ_LEN},k={K}").unwrap();
+        writeln!(dump, "Anchors for forward strand [{anchors}]").unwrap();
+        writeln!(dump, "Anchors for reverse strand []").unwrap();
+        writeln!(
+            dump,
+            "Chains[{{ref_id=0,score=100,query_start=0,query_end={READ_LEN},ref_start={ref_start},ref_end={},is_revcomp=false,anchors=[{anchors}]}}]",
+            ref_start + READ_LEN
+        )
+        .unwrap();
+        writeln!(
+            dump,
+            "Cigars:[({READ_LEN}M,was_considered=1,rstart={ref_start},ssw={READ_LEN}M,ssw_rstart={ref_start})]"
+        )
+        .unwrap();
+        writeln!(dump, "Done!").unwrap();
+    }
+    dump
+}
+
+/// A read with one chain whose anchors also fill the background.
+fn synthetic_read(anchors: u32) -> Read {
+    let anchors: Vec<Anchor> = (0..anchors)
+        .map(|idx| {
+            let query_start = idx * READ_LEN / anchors;
+            Anchor {
+                ref_start: 10_000 + query_start,
+                query_start,
+            }
+        })
+        .collect();
+    Read {
+        name: "bench".to_owned(),
+        read_len: READ_LEN,
+        k: K,
+        fwd_anchors: anchors.clone(),
+        rev_anchors: Vec::new(),
+        chains: vec![Chain {
+            ref_id: 0,
+            score: 100.0,
+            mapq: None,
+            qspan: [0, READ_LEN],
+            rspan: [10_000, 10_000 + READ_LEN],
+            is_revcomp: false,
+            anchors,
+            cigar: format!("{READ_LEN}M"),
+            ref_start: 10_000,
+            considered: true,
+            ssw_cigar: format!("{READ_LEN}M"),
+            ssw_ref_start: 10_000,
+        }],
+        cigar_count: None,
+    }
+}
+
+fn options() -> PlotOptions {
+    PlotOptions {
+        size: (800, 800),
+        verbosity: Verbosity::Quiet,
+        highlight_tolerance: 0,
+        ..Default::default()
+    }
+}
+
+fn parse(c: &mut Criterion) {
+    const READS: usize = 1000;
+    let dump = synthetic_dump(READS, 50);
+
+    let mut group = c.benchmark_group("parse_file");
+    group.throughput(Throughput::Elements(READS as u64));
+    group.bench_function("reads", |b| {
+        b.iter(|| {
+            parse_file(
+                black_box(dump.as_bytes()),
+                None,
+                false,
+                &ReadFilter::default(),
+            )
+        })
+    });
+    group.finish();
+}
+
+/// Reading a dump file and parsing it, either after loading the whole file or
+/// with `parse_reader` overlapping the two.
+fn read_and_parse(c: &mut Criterion) {
+    const READS: usize = 20_000;
+    let path = std::env::temp_dir().join("extract_chains_bench.txt");
+    std::fs::write(&path, synthetic_dump(READS, 50)).unwrap();
+    let path = path.to_str().unwrap();
+    let filter = ReadFilter::default();
+
+    let mut group = c.benchmark_group("read_and_parse");
+    group.throughput(Throughput::Elements(READS as u64));
+    group.sample_size(10);
+    group.bench_function("read_then_parse", |b| {
+        b.iter(|| parse_file(read_input(path).unwrap().as_bytes(), None, false, &filter))
+    });
+    group.bench_function("pipelined", |b| {
+        b.iter(|| parse_reader(open_input(path).unwrap(), None, false, &filter).unwrap())
+    });
+    group.finish();
+}
+
+fn plot(c: &mut Criterion) {
+    let options = options();
+    let read_dir = std::env::temp_dir().join("extract_chains_bench");
+    std::fs::create_dir_all(&read_dir).unwrap();
+
+    let mut group = c.benchmark_group("plot_chain");
+    group.sample_size(10);
+    for anchors in [100, 1_000, 10_000] {
+        let read = synthetic_read(anchors);
+        group.throughput(Throughput::Elements(u64::from(anchors)));
+        group.bench_with_input(BenchmarkId::from_parameter(anchors), &read, |b, read| {
+            b.iter(|| plot_chain(read, &read.chains[0], 0, &read_dir, &options))
+        });
+    }
+    group.finish();
+}
+
+/// Plots drawn into memory, without the PNG encoding and disk writes that make
+/// `plot_chain` timings noisy.
+fn render(c: &mut Criterion) {
+    let options = options();
+    let read = synthetic_read(100);
+    c.bench_function("render_chain_rgba", |b| {
+        b.iter(|| render_chain_rgba(black_box(&read), 0, &options))
+    });
+}
+
+criterion_group!(benches, parse, read_and_parse, plot, render);
+criterion_main!(benches);
@@ -0,0 +1,350 @@
+use std::fmt;
+
+use crate::Chain;
+
+/// Operations accepted by [`validate_cigar`].
+const CIGAR_OPS: &str = "MIDNSHP=X";
+
+/// Why a CIGAR string was rejected, with the byte offset of the problem.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CigarError {
+    UnknownOp {
+        op: char,
+        offset: usize,
+    },
+    MissingCount {
+        op: char,
+        offset: usize,
+    },
+    /// Digits at the end of the string with no operation after them.
+    MissingOp {
+        offset: usize,
+    },
+}
+
+impl fmt::Display for CigarError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CigarError::UnknownOp { op, offset } => {
+                write!(f, "unknown CIGAR operation {op:?} at offset {offset}")
+            }
+            CigarError::MissingCount { op, offset } => {
+                write!(f, "CIGAR operation {op:?} at offset {offset} has no count")
+            }
+            CigarError::MissingOp { offset } => {
+                write!(f, "CIGAR count at offset {offset} has no operation")
+            }
+        }
+    }
+}
+
+impl std::error::Error for CigarError {}
+
+/// Checks that a CIGAR string is a sequence of `<count><op>` pairs with
+/// known operations. [`cigar_ops`] itself tolerates all of these mistakes.
+pub fn validate_cigar(cigar: &str) -> Result<(), CigarError> {
+    let mut count_start = None;
+    for (offset, c) in cigar.char_indices() {
+        if c.is_ascii_digit() {
+            count_start.get_or_insert(offset);
+            continue;
+        }
+        if !CIGAR_OPS.contains(c) {
+            return Err(CigarError::UnknownOp { op: c, offset });
+        }
+        if count_start.take().is_none() {
+            return Err(CigarError::MissingCount { op: c, offset });
+        }
+    }
+    match count_start {
+        Some(offset) => Err(CigarError::MissingOp { offset }),
+        None => Ok(()),
+    }
+}
+
+/// Splits a CIGAR string into `(count, operation)` pairs. A missing count is
+/// read as zero and trailing digits without an operation are ignored.
+pub fn cigar_ops(cigar: &str) -> Vec<(u32, char)> {
+    let mut ops = Vec::new();
+    let mut i = 0;
+    let chars: Vec<char> = cigar.chars().collect();
+
+    while i < chars.len() {
+        let mut num_str = String::new();
+        while i < chars.len() && chars[i].is_ascii_digit() {
+            num_str.push(chars[i]);
+            i += 1;
+        }
+
+        if i >= chars.len() {
+            break;
+        }
+
+        let count: u32 = num_str.parse().unwrap_or(0);
+        ops.push((count, chars[i]));
+        i += 1;
+    }
+
+    ops
+}
+
+/// One CIGAR operation's stretch of an alignment path, in `(reference, query)`
+/// coordinates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PathSegment {
+    pub start: (u32, u32),
+    pub end: (u32, u32),
+    pub op: char,
+    pub len: u32,
+}
+
+/// Converts a CIGAR string into one path segment per operation that moves
+/// along the reference or query. Split runs such as `10M5M` are merged into
+/// one segment first. Positions saturate at `u32::MAX` rather than overflow.
+pub fn parse_cigar_to_segments(cigar: &str, ref_start: u32) -> Vec<PathSegment> {
+    let mut segments = Vec::new();
+    let mut ref_pos = ref_start;
+    let mut query_pos = 0u32;
+
+    for (count, operation) in merge_runs(cigar_ops(cigar)) {
+        let start = (ref_pos, query_pos);
+        match operation {
+            'M' | '=' | 'X' => {
+                ref_pos = ref_pos.saturating_add(count);
+                query_pos = query_pos.saturating_add(count);
+            }
+            'I' | 'S' => query_pos = query_pos.saturating_add(count),
+            'D' | 'N' => ref_pos = ref_pos.saturating_add(count),
+            // Hard clips and padding consume neither sequence, so the path
+            // does not move.
+            _ => continue,
+        }
+        segments.push(PathSegment {
+            start,
+            end: (ref_pos, query_pos),
+            op: operation,
+            len: count,
+        });
+    }
+
+    segments
+}
+
+/// Converts a CIGAR string into the `(reference, query)` vertices of its alignment path.
+pub fn parse_cigar_to_path(cigar: &str, ref_start: u32) -> Vec<(u32, u32)> {
+    let mut path = vec![(ref_start, 0)];
+    path.extend(
+        parse_cigar_to_segments(cigar, ref_start)
+            .iter()
+            .map(|segment| segment.end),
+    );
+    path
+}
+
+/// Returns how many more query bases the CIGAR consumes than the read has;
+/// negative when the CIGAR is shorter than the read.
+pub fn query_length_discrepancy(cigar: &str, read_len: u32) -> i64 {
+    let query_len: i64 = cigar_ops(cigar)
+        .into_iter()
+        .filter(|(_, op)| matches!(op, 'M' | '=' | 'X' | 'I' | 'S'))
+        .map(|(count, _)| i64::from(count))
+        .sum();
+    query_len - i64::from(read_len)
+}
+
+/// Insertion and deletion counts of a CIGAR.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GapStats {
+    pub insertions: u32,
+    pub inserted_bases: u32,
+    pub deletions: u32,
+    pub deleted_bases: u32,
+    /// Length of the longest single insertion or deletion.
+    pub largest_gap: u32,
+}
+
+/// Counts the `I` and `D` operations of a CIGAR; all zeros when it has none.
+/// Totals saturate at `u32::MAX`.
+pub fn gap_stats(cigar: &str) -> GapStats {
+    let mut stats = GapStats::default();
+    for (count, op) in cigar_ops(cigar) {
+        match op {
+            'I' => {
+                stats.insertions = stats.insertions.saturating_add(1);
+                stats.inserted_bases = stats.inserted_bases.saturating_add(count);
+            }
+            'D' => {
+                stats.deletions = stats.deletions.saturating_add(1);
+                stats.deleted_bases = stats.deleted_bases.saturating_add(count);
+            }
+            _ => continue,
+        }
+        stats.largest_gap = stats.largest_gap.max(count);
+    }
+    stats
+}
+
+/// Returns the fraction of aligned columns (`=`, `X`, `I`, `D`) that are
+/// matches. Identity is undefined, and `None` is returned, when the CIGAR
+/// uses the ambiguous `M` operation or has no aligned columns at all.
+pub fn identity(cigar: &str) -> Option<f64> {
+    let mut matches = 0u64;
+    let mut columns = 0u64;
+    for (count, op) in cigar_ops(cigar) {
+        match op {
+            'M' => return None,
+            '=' => {
+                matches += u64::from(count);
+                columns += u64::from(count);
+            }
+            'X' | 'I' | 'D' => columns += u64::from(count),
+            _ => {}
+        }
+    }
+    (columns > 0).then(|| matches as f64 / columns as f64)
+}
+
+/// Returns the residue matches and alignment block length of a CIGAR, as in
+/// PAF columns 10 and 11. `M` columns count as matches, since the CIGAR alone
+/// cannot tell them from mismatches; skips and clips are not part of the block.
+pub fn paf_counts(cigar: &str) -> (u32, u32) {
+    let mut matches = 0;
+    let mut block_len = 0;
+    for (count, op) in cigar_ops(cigar) {
+        match op {
+            'M' | '=' => {
+                matches += count;
+                block_len += count;
+            }
+            'X' | 'I' | 'D' => block_len += count,
+            _ => {}
+        }
+    }
+    (matches, block_len)
+}
+
+/// Returns the soft-clipped query bases at the start and end of a CIGAR,
+/// looking past hard clips. A CIGAR that is all clips counts as leading.
+pub fn soft_clips(cigar: &str) -> (u32, u32) {
+    let ops: Vec<_> = cigar_ops(cigar)
+        .into_iter()
+        .filter(|(_, op)| *op != 'H')
+        .collect();
+    let is_clip = |(_, op): &&(u32, char)| *op == 'S';
+    let leading = ops.iter().take_while(is_clip).count();
+    let trailing = ops[leading..].iter().rev().take_while(is_clip).count();
+    let bases = |runs: &[(u32, char)]| runs.iter().map(|(count, _)| count).sum();
+    (bases(&ops[..leading]), bases(&ops[ops.len() - trailing..]))
+}
+
+/// Returns the CIGAR without its soft and hard clips, as carried by PAF
+/// `cg:Z:` tags.
+pub fn strip_clips(cigar: &str) -> String {
+    cigar_ops(cigar)
+        .into_iter()
+        .filter(|(_, op)| !matches!(op, 'S' | 'H'))
+        .map(|(count, op)| format!("{count}{op}"))
+        .collect()
+}
+
+/// Merges adjacent runs of the same operation, saturating at `u32::MAX`, and
+/// drops empty ones.
+fn merge_runs(ops: impl IntoIterator<Item = (u32, char)>) -> Vec<(u32, char)> {
+    let mut runs: Vec<(u32, char)> = Vec::new();
+    for (count, op) in ops {
+        match runs.last_mut() {
+            _ if count == 0 => {}
+            Some((last_count, last_op)) if *last_op == op => {
+                *last_count = last_count.saturating_add(count)
+            }
+            _ => runs.push((count, op)),
+        }
+    }
+    runs
+}
+
+/// Rewrites a CIGAR with consecutive runs of the same operation collapsed and
+/// zero-count operations dropped, so `0M10M5M` becomes `15M`.
+pub fn normalize_cigar(cigar: &str) -> String {
+    merge_runs(cigar_ops(cigar))
+        .into_iter()
+        .map(|(count, op)| format!("{count}{op}"))
+        .collect()
+}
+
+/// Levenshtein distance between the run sequences of two CIGARs, each
+/// `<count><op>` run being one symbol, after merging adjacent runs of the same
+/// operation, so 0 means the CIGARs are the same once normalized. With
+/// `matches_as_m`, `=` and `X` are read as `M` first, so a CIGAR that tells
+/// matches from mismatches equals one that does not.
+pub fn cigar_distance(a: &str, b: &str, matches_as_m: bool) -> usize {
+    let runs = |cigar: &str| {
+        merge_runs(cigar_ops(cigar).into_iter().map(|(count, op)| match op {
+            '=' | 'X' if matches_as_m => (count, 'M'),
+            _ => (count, op),
+        }))
+    };
+    let (a, b) = (runs(a), runs(b));
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, run_a) in a.iter().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, run_b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(run_a != run_b);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Range of query positions a path covers at reference position `x`, or `None`
+/// when `x` lies outside the path. Vertical (insertion) segments widen it.
+fn query_range_at(path: &[(u32, u32)], x: u32) -> Option<(f64, f64)> {
+    let mut range: Option<(f64, f64)> = None;
+    for segment in path.windows(2) {
+        let [(x0, y0), (x1, y1)] = [segment[0], segment[1]];
+        if x < x0 || x > x1 {
+            continue;
+        }
+        let (lo, hi) = if x0 == x1 {
+            (f64::from(y0.min(y1)), f64::from(y0.max(y1)))
+        } else {
+            let t = f64::from(x - x0) / f64::from(x1 - x0);
+            let y = f64::from(y0) + t * (f64::from(y1) - f64::from(y0));
+            (y, y)
+        };
+        range = Some(range.map_or((lo, hi), |(l, h)| (l.min(lo), h.max(hi))));
+    }
+    range
+}
+
+/// Maximum vertical distance between two alignment paths over the reference
+/// positions both cover, or `None` when they do not overlap. Both paths are
+/// piecewise linear, so it is enough to measure at their vertices.
+pub fn path_divergence(a: &[(u32, u32)], b: &[(u32, u32)]) -> Option<f64> {
+    let one_way = |from: &[(u32, u32)], to: &[(u32, u32)]| {
+        from.iter()
+            .filter_map(|&(x, y)| {
+                let (lo, hi) = query_range_at(to, x)?;
+                let y = f64::from(y);
+                Some((lo - y).max(y - hi).max(0.0))
+            })
+            .reduce(f64::max)
+    };
+    match (one_way(a, b), one_way(b, a)) {
+        (Some(ab), Some(ba)) => Some(ab.max(ba)),
+        (ab, ba) => ab.or(ba),
+    }
+}
+
+/// Divergence between the piecewise and SSW paths of a chain, `None` when
+/// either CIGAR is missing.
+pub fn chain_divergence(chain: &Chain) -> Option<f64> {
+    if chain.cigar.is_empty() || chain.ssw_cigar.is_empty() {
+        return None;
+    }
+    path_divergence(
+        &parse_cigar_to_path(&chain.cigar, chain.ref_start),
+        &parse_cigar_to_path(&chain.ssw_cigar, chain.ssw_ref_start),
+    )
+}
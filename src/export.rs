@@ -0,0 +1,392 @@
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, HashSet},
+    io::{self, Write},
+};
+
+use crate::{
+    Read,
+    cigar::{cigar_distance, gap_stats, identity, normalize_cigar, paf_counts, strip_clips},
+    fasta::{IndexedFasta, reverse_complement},
+    plot::PlottedRead,
+    refs::RefNames,
+};
+
+const CHAIN_COLUMNS: [&str; 20] = [
+    "read",
+    "ref_id",
+    "score",
+    "qstart",
+    "qend",
+    "rstart",
+    "rend",
+    "is_revcomp",
+    "num_anchors",
+    "considered",
+    "cigar",
+    "ssw_cigar",
+    "identity",
+    "insertions",
+    "inserted_bases",
+    "deletions",
+    "deleted_bases",
+    "largest_gap",
+    "query_coverage",
+    "mapq",
+];
+
+const READ_COLUMNS: [&str; 6] = [
+    "read",
+    "read_len",
+    "num_chains",
+    "best_score",
+    "best_is_revcomp",
+    "any_considered",
+];
+
+/// How an export was produced: the tool version, the input and the active
+/// filter settings, keyed by option name.
+#[derive(Debug, Clone, Serialize)]
+pub struct Provenance {
+    pub version: &'static str,
+    pub input: String,
+    pub filters: BTreeMap<&'static str, String>,
+}
+
+impl Provenance {
+    /// The provenance as a `#` comment line, without its line break.
+    pub fn comment(&self) -> String {
+        let mut comment = format!("# extract_chains {} input={}", self.version, self.input);
+        for (option, value) in &self.filters {
+            comment.push_str(&format!(" {option}={value}"));
+        }
+        comment
+    }
+}
+
+#[derive(Serialize)]
+struct JsonExport<'a> {
+    meta: &'a Provenance,
+    reads: &'a [Read],
+}
+
+/// Serializes the reads, with their chains and anchors, as a JSON object
+/// holding the provenance under `meta` and the reads under `reads`.
+pub fn write_json<W: Write>(
+    mut out: W,
+    reads: &[Read],
+    pretty: bool,
+    provenance: &Provenance,
+) -> io::Result<()> {
+    let export = JsonExport {
+        meta: provenance,
+        reads,
+    };
+    if pretty {
+        serde_json::to_writer_pretty(&mut out, &export)?;
+    } else {
+        serde_json::to_writer(&mut out, &export)?;
+    }
+    writeln!(out)?;
+    out.flush()
+}
+
+/// Writes the manifest mapping each read and chain to its plot file.
+pub fn write_manifest<W: Write>(mut out: W, plotted: &[PlottedRead]) -> io::Result<()> {
+    serde_json::to_writer_pretty(&mut out, plotted)?;
+    writeln!(out)?;
+    out.flush()
+}
+
+/// Writes one tab-separated row per chain, preceded by the provenance comment
+/// and a header row. The `ref_id` column holds reference names where
+/// `ref_names` has them, and `identity` is a percentage, as in plot captions.
+pub fn write_chains_tsv<W: Write>(
+    mut out: W,
+    reads: &[Read],
+    ref_names: &RefNames,
+    provenance: &Provenance,
+) -> io::Result<()> {
+    writeln!(out, "{}", provenance.comment())?;
+    writeln!(out, "{}", CHAIN_COLUMNS.join("\t"))?;
+    for read in reads {
+        for chain in &read.chains {
+            let gaps = gap_stats(&chain.cigar);
+            writeln!(
+                out,
+                "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                read.name,
+                ref_names.name(chain.ref_id),
+                chain.score,
+                chain.qspan[0],
+                chain.qspan[1],
+                chain.rspan[0],
+                chain.rspan[1],
+                chain.is_revcomp,
+                chain.anchors.len(),
+                chain.considered,
+                normalize_cigar(&chain.cigar),
+                normalize_cigar(&chain.ssw_cigar),
+                identity(&chain.cigar)
+                    .map_or(String::new(), |identity| format!("{:.2}", identity * 100.0)),
+                gaps.insertions,
+                gaps.inserted_bases,
+                gaps.deletions,
+                gaps.deleted_bases,
+                gaps.largest_gap,
+                chain
+                    .query_coverage(read.read_len)
+                    .map_or(String::new(), |coverage| format!("{coverage:.4}")),
+                chain.mapq.map_or(String::new(), |mapq| mapq.to_string()),
+            )?;
+        }
+    }
+    out.flush()
+}
+
+/// Writes one tab-separated row per read, summarizing its chains, preceded by
+/// the provenance comment and a header row. The best-chain columns are empty
+/// for reads without chains.
+pub fn write_read_summary_tsv<W: Write>(
+    mut out: W,
+    reads: &[Read],
+    provenance: &Provenance,
+) -> io::Result<()> {
+    writeln!(out, "{}", provenance.comment())?;
+    writeln!(out, "{}", READ_COLUMNS.join("\t"))?;
+    for read in reads {
+        let best = read
+            .chains
+            .iter()
+            .max_by(|a, b| a.score.total_cmp(&b.score));
+        writeln!(
+            out,
+            "{}\t{}\t{}\t{}\t{}\t{}",
+            read.name,
+            read.read_len,
+            read.chains.len(),
+            best.map_or(String::new(), |chain| chain.score.to_string()),
+            best.map_or(String::new(), |chain| chain.is_revcomp.to_string()),
+            read.chains.iter().any(|chain| chain.considered),
+        )?;
+    }
+    out.flush()
+}
+
+const ANCHOR_COLUMNS: [&str; 6] = [
+    "read",
+    "strand",
+    "ref_start",
+    "query_start",
+    "k",
+    "in_chain",
+];
+
+/// Writes one tab-separated row per anchor of every read, forward strand
+/// first, after the provenance comment and a header row. Background anchors
+/// that some chain of the same strand uses are flagged `in_chain`, and chain
+/// anchors missing from the background are added after them.
+pub fn write_anchors_tsv<W: Write>(
+    mut out: W,
+    reads: &[Read],
+    provenance: &Provenance,
+) -> io::Result<()> {
+    writeln!(out, "{}", provenance.comment())?;
+    writeln!(out, "{}", ANCHOR_COLUMNS.join("\t"))?;
+    for read in reads {
+        for (is_revcomp, background) in [(false, &read.fwd_anchors), (true, &read.rev_anchors)] {
+            let strand = if is_revcomp { "rev" } else { "fwd" };
+            let chained: HashSet<(u32, u32)> = read
+                .chains
+                .iter()
+                .filter(|chain| chain.is_revcomp == is_revcomp)
+                .flat_map(|chain| &chain.anchors)
+                .map(|anchor| (anchor.ref_start, anchor.query_start))
+                .collect();
+            let mut seen = HashSet::new();
+            for anchor in background {
+                let key = (anchor.ref_start, anchor.query_start);
+                seen.insert(key);
+                writeln!(
+                    out,
+                    "{}\t{strand}\t{}\t{}\t{}\t{}",
+                    read.name,
+                    anchor.ref_start,
+                    anchor.query_start,
+                    read.k,
+                    chained.contains(&key),
+                )?;
+            }
+            let chain_only = read
+                .chains
+                .iter()
+                .filter(|chain| chain.is_revcomp == is_revcomp)
+                .flat_map(|chain| &chain.anchors);
+            for anchor in chain_only {
+                if seen.insert((anchor.ref_start, anchor.query_start)) {
+                    writeln!(
+                        out,
+                        "{}\t{strand}\t{}\t{}\t{}\ttrue",
+                        read.name, anchor.ref_start, anchor.query_start, read.k,
+                    )?;
+                }
+            }
+        }
+    }
+    out.flush()
+}
+
+const CIGAR_DIFF_COLUMNS: [&str; 4] = ["read", "chain", "identical", "distance"];
+
+/// Writes one tab-separated row per chain with both a piecewise and an SSW
+/// CIGAR, giving the run-level edit distance between the two, after the
+/// provenance comment and a header row. `matches_as_m` is passed on to
+/// [`cigar_distance`].
+pub fn write_cigar_diff_tsv<W: Write>(
+    mut out: W,
+    reads: &[Read],
+    matches_as_m: bool,
+    provenance: &Provenance,
+) -> io::Result<()> {
+    writeln!(out, "{}", provenance.comment())?;
+    writeln!(out, "{}", CIGAR_DIFF_COLUMNS.join("\t"))?;
+    for read in reads {
+        for (idx, chain) in read.chains.iter().enumerate() {
+            if chain.cigar.is_empty() || chain.ssw_cigar.is_empty() {
+                continue;
+            }
+            let distance = cigar_distance(&chain.cigar, &chain.ssw_cigar, matches_as_m);
+            writeln!(out, "{}\t{idx}\t{}\t{distance}", read.name, distance == 0)?;
+        }
+    }
+    out.flush()
+}
+
+/// The highest score BED allows.
+const BED_MAX_SCORE: f64 = 1000.0;
+
+/// A chain score as a BED score: rounded and clamped to 0-1000.
+fn bed_score(score: f64) -> u32 {
+    score.round().clamp(0.0, BED_MAX_SCORE) as u32
+}
+
+/// Writes one BED line per considered chain: reference, span, read name,
+/// score and strand, after the provenance comment. BED scores are integers
+/// from 0 to 1000, so the chain score is rounded and clamped to that range;
+/// the chain TSV keeps the exact score.
+pub fn write_bed<W: Write>(
+    mut out: W,
+    reads: &[Read],
+    ref_names: &RefNames,
+    provenance: &Provenance,
+) -> io::Result<()> {
+    writeln!(out, "{}", provenance.comment())?;
+    for read in reads {
+        for chain in read.chains.iter().filter(|chain| chain.considered) {
+            writeln!(
+                out,
+                "{}\t{}\t{}\t{}\t{}\t{}",
+                ref_names.name(chain.ref_id),
+                chain.rspan[0],
+                chain.rspan[1],
+                read.name,
+                bed_score(chain.score),
+                if chain.is_revcomp { '-' } else { '+' },
+            )?;
+        }
+    }
+    out.flush()
+}
+
+/// Writes one PAF line per considered chain. The dump does not record
+/// reference lengths, so the target length column comes from `ref_names` and
+/// is 0 for references it has no length for; a warning counts them.
+/// Scores are written as `AS:i:` tags and CIGARs, without clips, as `cg:Z:`
+/// tags, so the output reads back with the PAF input format.
+pub fn write_paf<W: Write>(mut out: W, reads: &[Read], ref_names: &RefNames) -> io::Result<()> {
+    let mut unknown_lengths = 0;
+    for read in reads {
+        for chain in read.chains.iter().filter(|chain| chain.considered) {
+            let (matches, block_len) = if chain.cigar.is_empty() {
+                let qlen = chain.qspan[1].saturating_sub(chain.qspan[0]);
+                let rlen = chain.rspan[1].saturating_sub(chain.rspan[0]);
+                (0, qlen.max(rlen))
+            } else {
+                paf_counts(&chain.cigar)
+            };
+            let target_len = ref_names.length(chain.ref_id).unwrap_or_else(|| {
+                unknown_lengths += 1;
+                0
+            });
+            write!(
+                out,
+                "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\ttp:A:P\tAS:i:{}",
+                read.name,
+                read.read_len,
+                chain.qspan[0],
+                chain.qspan[1],
+                if chain.is_revcomp { '-' } else { '+' },
+                ref_names.name(chain.ref_id),
+                target_len,
+                chain.rspan[0],
+                chain.rspan[1],
+                matches,
+                block_len,
+                chain.mapq.unwrap_or(255),
+                chain.score.round() as i64,
+            )?;
+            if !chain.cigar.is_empty() {
+                write!(
+                    out,
+                    "\tcg:Z:{}",
+                    normalize_cigar(&strip_clips(&chain.cigar))
+                )?;
+            }
+            writeln!(out)?;
+        }
+    }
+    if unknown_lengths > 0 {
+        log::warn!(
+            "reference lengths unknown for {unknown_lengths} PAF records; \
+             their target length is written as 0"
+        );
+    }
+    out.flush()
+}
+
+/// Bases per line of written FASTA records.
+const FASTA_LINE_WIDTH: usize = 60;
+
+/// Writes the reference sequence under every considered chain's span as a
+/// FASTA record named after the read, reverse complemented for reverse-strand
+/// chains. Chains on contigs missing from `reference` are skipped with a
+/// warning.
+pub fn write_ref_fasta<W: Write>(
+    mut out: W,
+    reads: &[Read],
+    ref_names: &RefNames,
+    reference: &mut IndexedFasta,
+) -> io::Result<()> {
+    for read in reads {
+        for chain in read.chains.iter().filter(|chain| chain.considered) {
+            let contig = ref_names.name(chain.ref_id);
+            let [start, end] = chain.rspan.map(u64::from);
+            let Some(mut seq) = reference.fetch(&contig, start, end)? else {
+                log::warn!("read {}: no contig {contig} in the reference", read.name);
+                continue;
+            };
+            let strand = if chain.is_revcomp {
+                seq = reverse_complement(&seq);
+                '-'
+            } else {
+                '+'
+            };
+            writeln!(out, ">{} {contig}:{start}-{end}({strand})", read.name)?;
+            for line in seq.chunks(FASTA_LINE_WIDTH) {
+                out.write_all(line)?;
+                writeln!(out)?;
+            }
+        }
+    }
+    out.flush()
+}
@@ -0,0 +1,172 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufRead, BufReader, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+/// Where a contig's sequence lies in the FASTA file, as in a `.fai` index.
+#[derive(Debug, Clone, Copy)]
+struct IndexEntry {
+    len: u64,
+    offset: u64,
+    line_bases: u64,
+    line_width: u64,
+}
+
+/// Random access to the contigs of an uncompressed FASTA file. The `.fai`
+/// index next to the file is used when present; otherwise the file is scanned
+/// once to build it.
+#[derive(Debug)]
+pub struct IndexedFasta {
+    file: File,
+    index: HashMap<String, IndexEntry>,
+}
+
+fn invalid_data(path: &Path, message: String) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("{}: {message}", path.display()),
+    )
+}
+
+impl IndexedFasta {
+    pub fn open(path: &Path) -> io::Result<IndexedFasta> {
+        let mut fai = path.as_os_str().to_owned();
+        fai.push(".fai");
+        let fai = PathBuf::from(fai);
+        let index = if fai.exists() {
+            read_fai(&fai)?
+        } else {
+            build_index(path)?
+        };
+        Ok(IndexedFasta {
+            file: File::open(path)?,
+            index,
+        })
+    }
+
+    /// Reads bases `start..end` of a contig, clamped to its length. Returns
+    /// `None` when the FASTA has no such contig.
+    pub fn fetch(&mut self, contig: &str, start: u64, end: u64) -> io::Result<Option<Vec<u8>>> {
+        let Some(entry) = self.index.get(contig).copied() else {
+            return Ok(None);
+        };
+        let end = end.min(entry.len);
+        let start = start.min(end);
+        if start == end {
+            return Ok(Some(Vec::new()));
+        }
+        let byte_at = |pos: u64| {
+            entry.offset + pos / entry.line_bases * entry.line_width + pos % entry.line_bases
+        };
+
+        let from = byte_at(start);
+        let mut bytes = vec![0; (byte_at(end) - from) as usize];
+        self.file.seek(SeekFrom::Start(from))?;
+        self.file.read_exact(&mut bytes)?;
+        bytes.retain(|b| !b.is_ascii_whitespace());
+        Ok(Some(bytes))
+    }
+}
+
+fn read_fai(path: &Path) -> io::Result<HashMap<String, IndexEntry>> {
+    let mut index = HashMap::new();
+    for (line_idx, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+        let line = line?;
+        let fields: Vec<&str> = line.split('\t').collect();
+        let number = |idx: usize| {
+            fields
+                .get(idx)
+                .and_then(|field| field.trim().parse::<u64>().ok())
+                .ok_or_else(|| invalid_data(path, format!("line {}: malformed", line_idx + 1)))
+        };
+        index.insert(
+            fields[0].to_owned(),
+            IndexEntry {
+                len: number(1)?,
+                offset: number(2)?,
+                line_bases: number(3)?,
+                line_width: number(4)?,
+            },
+        );
+    }
+    Ok(index)
+}
+
+/// Scans a FASTA file for its contigs. Every sequence line of a contig but
+/// the last must have the same length, as `samtools faidx` requires.
+fn build_index(path: &Path) -> io::Result<HashMap<String, IndexEntry>> {
+    let mut index = HashMap::new();
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut offset = 0;
+    let mut line = String::new();
+    let mut contig: Option<(String, IndexEntry)> = None;
+    let mut short_line = false;
+
+    loop {
+        line.clear();
+        let width = reader.read_line(&mut line)? as u64;
+        if width == 0 {
+            break;
+        }
+        offset += width;
+
+        if let Some(name) = line.strip_prefix('>') {
+            index.extend(contig.take());
+            let name = name
+                .split_whitespace()
+                .next()
+                .unwrap_or_default()
+                .to_owned();
+            let entry = IndexEntry {
+                len: 0,
+                offset,
+                line_bases: 0,
+                line_width: 0,
+            };
+            contig = Some((name, entry));
+            short_line = false;
+            continue;
+        }
+
+        let Some((name, entry)) = &mut contig else {
+            continue;
+        };
+        let bases = line.trim_end().len() as u64;
+        if bases == 0 {
+            continue;
+        }
+        if entry.line_bases == 0 {
+            entry.line_bases = bases;
+            entry.line_width = width;
+        } else if short_line || bases > entry.line_bases {
+            return Err(invalid_data(
+                path,
+                format!("contig {name} has sequence lines of differing lengths"),
+            ));
+        }
+        short_line = bases < entry.line_bases;
+        entry.len += bases;
+    }
+    index.extend(contig);
+    Ok(index)
+}
+
+/// Reverse complement of a nucleotide sequence; other characters are kept.
+pub fn reverse_complement(seq: &[u8]) -> Vec<u8> {
+    seq.iter()
+        .rev()
+        .map(|base| match base {
+            b'A' => b'T',
+            b'C' => b'G',
+            b'G' => b'C',
+            b'T' => b'A',
+            b'a' => b't',
+            b'c' => b'g',
+            b'g' => b'c',
+            b't' => b'a',
+            other => *other,
+        })
+        .collect()
+}
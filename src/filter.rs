@@ -0,0 +1,136 @@
+use clap::ValueEnum;
+use regex::Regex;
+use std::{collections::HashSet, num::NonZeroUsize};
+
+use crate::{Chain, Read, cigar::chain_divergence};
+
+/// Read filters applied while parsing, before the `-n` limit.
+#[derive(Debug, Clone, Default)]
+pub struct ReadFilter {
+    pub name_regex: Option<Regex>,
+    /// Only keep reads with one of these names.
+    pub names: Option<HashSet<String>>,
+    pub min_read_len: Option<u32>,
+    pub max_read_len: Option<u32>,
+    /// Keep only every kth of the reads the other filters accept, starting
+    /// with the first.
+    pub sample: Option<NonZeroUsize>,
+}
+
+impl ReadFilter {
+    pub fn keeps(&self, read: &Read) -> bool {
+        self.name_regex
+            .as_ref()
+            .is_none_or(|regex| regex.is_match(&read.name))
+            && self
+                .names
+                .as_ref()
+                .is_none_or(|names| names.contains(&read.name))
+            && self.min_read_len.is_none_or(|min| read.read_len >= min)
+            && self.max_read_len.is_none_or(|max| read.read_len <= max)
+    }
+
+    /// Whether the `idx`th read accepted by `keeps`, counting from 0, is part
+    /// of the sample.
+    pub fn samples(&self, idx: usize) -> bool {
+        self.sample.is_none_or(|k| idx % k == 0)
+    }
+}
+
+/// Reads a list of read names, the first word of every non-blank line, so
+/// tab-separated tables with the name first work too.
+pub fn parse_read_names(text: &str) -> HashSet<String> {
+    text.lines()
+        .filter_map(|line| line.split_whitespace().next())
+        .map(str::to_owned)
+        .collect()
+}
+
+/// Chain filters applied after parsing, before any plotting or export.
+#[derive(Debug, Clone, Default)]
+pub struct ChainFilter {
+    pub min_score: Option<f64>,
+    /// Reference ids to keep; empty keeps every reference.
+    pub ref_ids: Vec<u32>,
+    /// Only keep chains whose piecewise and SSW paths diverge by more than this.
+    pub min_divergence: Option<f64>,
+    /// Only keep chains whose query span covers at least this fraction of the
+    /// read.
+    pub min_query_coverage: Option<f64>,
+    /// Only keep the chains the aligner considered; with `-x` that is the
+    /// first chain of each read.
+    pub considered_only: bool,
+    /// Keep at most this many of the best-scoring chains per read, after the
+    /// other filters.
+    pub max_chains: Option<usize>,
+}
+
+impl ChainFilter {
+    pub fn keeps(&self, chain: &Chain, read_len: u32) -> bool {
+        (!self.considered_only || chain.considered)
+            && self.min_score.is_none_or(|min| chain.score >= min)
+            && self.min_query_coverage.is_none_or(|min| {
+                chain
+                    .query_coverage(read_len)
+                    .is_some_and(|coverage| coverage >= min)
+            })
+            && (self.ref_ids.is_empty() || self.ref_ids.contains(&chain.ref_id))
+            && self
+                .min_divergence
+                .is_none_or(|min| chain_divergence(chain).is_some_and(|d| d > min))
+    }
+
+    /// Drops the rejected chains of one read, returning how many were dropped.
+    pub fn apply_to(&self, read: &mut Read) -> usize {
+        let before = read.chains.len();
+        let read_len = read.read_len;
+        read.chains.retain(|chain| self.keeps(chain, read_len));
+        if let Some(max) = self.max_chains
+            && read.chains.len() > max
+        {
+            let mut by_score: Vec<usize> = (0..read.chains.len()).collect();
+            by_score.sort_by(|&a, &b| read.chains[b].score.total_cmp(&read.chains[a].score));
+            let mut keep = vec![false; read.chains.len()];
+            for &idx in &by_score[..max] {
+                keep[idx] = true;
+            }
+            let mut keep = keep.into_iter();
+            read.chains.retain(|_| keep.next().unwrap());
+        }
+        before - read.chains.len()
+    }
+
+    /// Drops rejected chains, then any read left without chains. Returns the
+    /// number of chains dropped.
+    pub fn apply(&self, reads: &mut Vec<Read>) -> usize {
+        let dropped = reads.iter_mut().map(|read| self.apply_to(read)).sum();
+        reads.retain(|read| !read.chains.is_empty());
+        dropped
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+pub enum ChainOrder {
+    /// Highest score first
+    Score,
+    /// By reference id, then reference start
+    Position,
+}
+
+impl ChainOrder {
+    /// Reorders a read's chains. With `mapping_only` the considered flag is
+    /// recomputed afterwards, since it only marks whichever chain comes first.
+    pub fn sort(self, read: &mut Read, mapping_only: bool) {
+        match self {
+            ChainOrder::Score => read.chains.sort_by(|a, b| b.score.total_cmp(&a.score)),
+            ChainOrder::Position => read
+                .chains
+                .sort_by_key(|chain| (chain.ref_id, chain.rspan[0])),
+        }
+        if mapping_only {
+            for (idx, chain) in read.chains.iter_mut().enumerate() {
+                chain.considered = idx == 0;
+            }
+        }
+    }
+}
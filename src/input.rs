@@ -0,0 +1,31 @@
+use flate2::read::MultiGzDecoder;
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, Read},
+};
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Opens the dump for buffered reading, decompressing it on the fly when it is
+/// gzipped. A path of `-` reads from standard input.
+pub fn open_input(path: &str) -> io::Result<Box<dyn BufRead + Send>> {
+    let mut reader: Box<dyn BufRead + Send> = if path == "-" {
+        Box::new(BufReader::new(io::stdin()))
+    } else {
+        Box::new(BufReader::new(File::open(path)?))
+    };
+    let is_gzip = path.ends_with(".gz") || reader.fill_buf()?.starts_with(&GZIP_MAGIC);
+
+    if is_gzip {
+        Ok(Box::new(BufReader::new(MultiGzDecoder::new(reader))))
+    } else {
+        Ok(reader)
+    }
+}
+
+/// Reads the whole dump into memory.
+pub fn read_input(path: &str) -> io::Result<String> {
+    let mut text = String::new();
+    open_input(path)?.read_to_string(&mut text)?;
+    Ok(text)
+}
@@ -0,0 +1,136 @@
+//! Parsing and plotting of chain dumps produced by the aligner's debug output.
+
+pub mod cigar;
+pub mod export;
+pub mod fasta;
+pub mod filter;
+pub mod input;
+pub mod log;
+pub mod overlap;
+pub mod paf;
+pub mod palette;
+pub mod parse;
+pub mod plot;
+pub mod refs;
+pub mod stats;
+pub mod stream;
+pub mod template;
+pub mod validate;
+
+use serde::Serialize;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Anchor {
+    pub ref_start: u32,
+    pub query_start: u32,
+}
+
+impl Anchor {
+    /// Whether the anchor's k-mer runs past the end of a `read_len`-long read.
+    pub fn overruns(&self, k: u32, read_len: u32) -> bool {
+        self.query_start.saturating_add(k) > read_len
+    }
+
+    /// Whether `next` starts at or after the end of this anchor's `k`-mer on
+    /// both the reference and the query, so the two can be chained in order.
+    pub fn precedes(&self, next: &Anchor, k: u32) -> bool {
+        next.ref_start >= self.ref_start.saturating_add(k)
+            && next.query_start >= self.query_start.saturating_add(k)
+    }
+
+    /// Whether both coordinates are within `tolerance` bases of `other`'s.
+    pub fn is_near(&self, other: &Anchor, tolerance: u32) -> bool {
+        self.ref_start.abs_diff(other.ref_start) <= tolerance
+            && self.query_start.abs_diff(other.query_start) <= tolerance
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Chain {
+    pub ref_id: u32,
+    pub score: f64,
+    /// Mapping quality, for inputs that report one.
+    pub mapq: Option<u8>,
+    pub qspan: [u32; 2],
+    pub rspan: [u32; 2],
+    pub is_revcomp: bool,
+    pub anchors: Vec<Anchor>,
+    pub cigar: String,
+    pub ref_start: u32,
+    pub considered: bool,
+    pub ssw_cigar: String,
+    pub ssw_ref_start: u32,
+}
+
+impl Chain {
+    /// Fraction of a `read_len`-long read covered by the chain's query span, or
+    /// `None` for an empty read.
+    pub fn query_coverage(&self, read_len: u32) -> Option<f64> {
+        (read_len > 0)
+            .then(|| f64::from(self.qspan[1].saturating_sub(self.qspan[0])) / f64::from(read_len))
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Read {
+    pub name: String,
+    pub read_len: u32,
+    pub k: u32,
+    pub fwd_anchors: Vec<Anchor>,
+    pub rev_anchors: Vec<Anchor>,
+    pub chains: Vec<Chain>,
+    /// Entries of the `Cigars` block, when CIGARs were parsed from a dump.
+    #[serde(skip)]
+    pub cigar_count: Option<usize>,
+}
+
+impl Read {
+    /// Moves every anchor, background or chain, whose k-mer runs past the end
+    /// of the read back so it ends on the read's last base. Returns how many
+    /// background anchors moved.
+    pub fn clamp_anchors(&mut self) -> usize {
+        let (k, read_len) = (self.k, self.read_len);
+        let mut clamp = |anchor: &mut Anchor| {
+            let overruns = anchor.overruns(k, read_len);
+            if overruns {
+                anchor.query_start = read_len.saturating_sub(k);
+            }
+            overruns
+        };
+        let clamped = self
+            .fwd_anchors
+            .iter_mut()
+            .chain(&mut self.rev_anchors)
+            .map(&mut clamp)
+            .filter(|&clamped| clamped)
+            .count();
+        for anchor in self.chains.iter_mut().flat_map(|chain| &mut chain.anchors) {
+            clamp(anchor);
+        }
+        clamped
+    }
+
+    /// Looks for anchors within `tolerance` bases of `target`. Returns `None`
+    /// when no anchor of the read, background or chain, is near it, and
+    /// otherwise the indices of the chains that have one.
+    pub fn find_anchor(&self, target: &Anchor, tolerance: u32) -> Option<Vec<usize>> {
+        let chains: Vec<usize> = self
+            .chains
+            .iter()
+            .enumerate()
+            .filter(|(_, chain)| {
+                chain
+                    .anchors
+                    .iter()
+                    .any(|anchor| anchor.is_near(target, tolerance))
+            })
+            .map(|(idx, _)| idx)
+            .collect();
+        let in_background = self
+            .fwd_anchors
+            .iter()
+            .chain(&self.rev_anchors)
+            .any(|anchor| anchor.is_near(target, tolerance));
+        (in_background || !chains.is_empty()).then_some(chains)
+    }
+}
@@ -0,0 +1,37 @@
+use ::log::LevelFilter;
+
+/// How much to log, through the `log` crate, and whether to show progress.
+/// Warnings and errors are logged at every level.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    /// No progress bar; only reports that were explicitly asked for, such as
+    /// `--stats`, and warnings.
+    Quiet,
+    /// A progress bar and warnings.
+    #[default]
+    Normal,
+    /// Also a summary of what was parsed and filtered.
+    Verbose,
+    /// Also every file written and every batch parsed.
+    Debug,
+}
+
+impl Verbosity {
+    pub fn from_flags(quiet: bool, verbose: u8) -> Verbosity {
+        match (quiet, verbose) {
+            (true, _) => Verbosity::Quiet,
+            (false, 0) => Verbosity::Normal,
+            (false, 1) => Verbosity::Verbose,
+            (false, _) => Verbosity::Debug,
+        }
+    }
+
+    /// The most detailed level logged; `RUST_LOG` overrides it.
+    pub fn level(self) -> LevelFilter {
+        match self {
+            Verbosity::Quiet | Verbosity::Normal => LevelFilter::Warn,
+            Verbosity::Verbose => LevelFilter::Info,
+            Verbosity::Debug => LevelFilter::Debug,
+        }
+    }
+}
@@ -1,56 +1,55 @@
-use clap::{Parser, ValueHint};
-use indicatif::{ParallelProgressIterator, ProgressBar, ProgressStyle};
-use plotters::{
-    chart::{ChartBuilder, SeriesLabelPosition},
-    prelude::{BitMapBackend, Cross, IntoDrawingArea, PathElement},
-    series::{LineSeries, PointSeries},
-    style::{
-        full_palette::{ORANGE, PURPLE},
-        Color, BLACK, BLUE, GREEN, RED, WHITE,
+use clap::{
+    Arg, ArgAction, ArgMatches, CommandFactory, FromArgMatches, Parser, ValueEnum, ValueHint,
+    error::ErrorKind, parser::ValueSource,
+};
+use extract_chains::{
+    Anchor, Read,
+    export::{
+        Provenance, write_anchors_tsv, write_bed, write_chains_tsv, write_cigar_diff_tsv,
+        write_json, write_manifest, write_paf, write_read_summary_tsv, write_ref_fasta,
+    },
+    fasta::IndexedFasta,
+    filter::{ChainFilter, ChainOrder, ReadFilter, parse_read_names},
+    input::{open_input, read_input},
+    log::Verbosity,
+    overlap::find_overlaps,
+    paf::parse_paf,
+    palette::{ColorPalette, Theme},
+    parse::ParseError,
+    plot::{
+        AnchorStyle, Axes, ColorBy, ImageFormat, Parallelism, PlotOptions, PlottedRead, ReadDirs,
+        Units, planned_images, plot_diff, plot_reads, plot_stream,
     },
+    refs::RefNames,
+    stats::{ScoreCheck, Stats},
+    stream::{ReadStream, StreamError, parse_reader},
+    template::TitleTemplate,
+    validate::check_all,
 };
-use rayon::prelude::*;
+use log::Level;
+use rayon::ThreadPoolBuilder;
+use regex::Regex;
+use serde::{Deserialize, Deserializer, Serialize, de};
 use std::{
-    fs::{create_dir_all, read_to_string},
-    io::Result,
-    path::Path,
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    process,
 };
 
-#[derive(Debug, Clone)]
-struct Anchor {
-    ref_start: u32,
-    query_start: u32,
-}
-
-#[derive(Debug, Clone)]
-struct Chain {
-    ref_id: u32,
-    score: f64,
-    qspan: [u32; 2],
-    rspan: [u32; 2],
-    is_revcomp: bool,
-    anchors: Vec<Anchor>,
-    cigar: String,
-    ref_start: u32,
-    considered: bool,
-    ssw_cigar: String,
-    ssw_ref_start: u32,
-}
-
-#[derive(Debug)]
-struct Read {
-    name: String,
-    read_len: u32,
-    k: u32,
-    fwd_anchors: Vec<Anchor>,
-    rev_anchors: Vec<Anchor>,
-    chains: Vec<Chain>,
-}
-
 #[derive(Parser, Debug)]
 struct Args {
-    #[arg(value_hint = ValueHint::FilePath)]
-    file: String,
+    /// Chain dumps to read, or `-` for standard input. With several inputs,
+    /// each one is plotted into a subdirectory of the output named after it
+    #[arg(value_hint = ValueHint::FilePath, required = true)]
+    file: Vec<String>,
+
+    /// TOML file of default options, keyed like the fields of `Args`;
+    /// options given on the command line take precedence
+    #[arg(long, value_hint = ValueHint::FilePath)]
+    config: Option<PathBuf>,
 
     #[arg(short = 'n')]
     n: Option<usize>,
@@ -60,596 +59,1174 @@ struct Args {
 
     #[arg(short = 'x')]
     mapping_only: bool,
+
+    /// Only keep reads whose name matches this regular expression
+    #[arg(long)]
+    name_regex: Option<Regex>,
+
+    /// Only keep reads named in this file, one name per line
+    #[arg(long, value_hint = ValueHint::FilePath)]
+    reads_file: Option<String>,
+
+    /// Only keep reads at least this long
+    #[arg(long, value_name = "BASES")]
+    min_read_len: Option<u32>,
+
+    /// Only keep reads at most this long
+    #[arg(long, value_name = "BASES")]
+    max_read_len: Option<u32>,
+
+    /// Keep only every kth read, after the name and length filters, to sample the whole input
+    #[arg(long, value_name = "K")]
+    sample: Option<NonZeroUsize>,
+
+    /// Drop chains scoring below this threshold
+    #[arg(long)]
+    min_score: Option<f64>,
+
+    /// Only keep chains on this reference id (repeatable)
+    #[arg(long = "ref-id")]
+    ref_ids: Vec<u32>,
+
+    /// Only keep chains whose piecewise and SSW paths diverge by more than this many bases
+    #[arg(long)]
+    min_divergence: Option<f64>,
+
+    /// Drop chains whose query span covers less than this fraction of the read
+    #[arg(long, value_name = "FRACTION", value_parser = parse_fraction)]
+    min_query_coverage: Option<f64>,
+
+    /// Only keep the chains the aligner considered, skipping reads without one
+    #[arg(long)]
+    considered_only: bool,
+
+    /// Keep only the best-scoring N chains of each read, after the other chain filters
+    #[arg(long, value_name = "N")]
+    max_chains_per_read: Option<usize>,
+
+    /// Warn about chain pairs whose query spans overlap by more than this fraction of the shorter one
+    #[arg(
+        long,
+        value_name = "FRACTION",
+        num_args = 0..=1,
+        default_missing_value = "0.9",
+        value_parser = parse_fraction
+    )]
+    report_overlaps: Option<f64>,
+
+    /// Reorder each read's chains before filtering, indexing and plotting
+    #[arg(long, value_enum)]
+    sort_chains: Option<ChainOrder>,
+
+    /// Log a summary of what was parsed and filtered; repeat to also log the
+    /// path of every plot written, in input order. `RUST_LOG` overrides it
+    #[arg(short, long, action = ArgAction::Count)]
+    verbose: u8,
+
+    /// Hide the progress bar and print nothing to stdout except explicitly
+    /// requested reports; warnings and errors are still logged to stderr
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Note CIGAR/read length mismatches in plot titles
+    #[arg(long)]
+    mark_cigar_mismatch: bool,
+
+    /// Draw all chains of a read in one plot instead of one plot per chain
+    #[arg(long)]
+    combined: bool,
+
+    /// With exactly two inputs, overlay the best considered chain of each for
+    /// every read instead of plotting the inputs separately
+    #[arg(long, conflicts_with_all = ["stream", "combined", "panels", "contact_sheet", "stats", "dry_run"])]
+    diff: bool,
+
+    /// Tile each read's chain plots into one image with this many columns
+    #[arg(long, value_name = "COLUMNS", conflicts_with = "combined")]
+    panels: Option<usize>,
+
+    /// Write each chain's plot under a subdirectory named after its reference
+    #[arg(long, conflicts_with_all = ["combined", "panels"])]
+    group_by_ref: bool,
+
+    /// Draw a scale bar of a round reference length in the corner of each chain plot
+    #[arg(long)]
+    scale_bar: bool,
+
+    /// Show this reference range in every plot, skipping chains outside it
+    #[arg(long, value_name = "START:END", value_parser = parse_ref_window)]
+    ref_window: Option<(u32, u32)>,
+
+    /// Draw a track of binned anchor counts along the reference axis of each plot
+    #[arg(long)]
+    coverage_track: bool,
+
+    /// Circle the anchors at this position in chain plots and report the reads and chains that have one
+    #[arg(long, value_name = "REF:QUERY", value_parser = parse_anchor)]
+    highlight_anchor: Option<Anchor>,
+
+    /// Bases either coordinate of a highlighted anchor may differ by
+    #[arg(
+        long,
+        value_name = "BASES",
+        default_value_t = 5,
+        requires = "highlight_anchor"
+    )]
+    highlight_tolerance: u32,
+
+    /// Draw the diagonal through each chain's start as a reference line
+    #[arg(long)]
+    diagonal: bool,
+
+    /// How background anchors are drawn; `heatmap` suits very dense reads
+    #[arg(long, value_enum, default_value_t = AnchorStyle::Lines)]
+    anchor_style: AnchorStyle,
+
+    /// Draw only every kth background anchor when a plot has more than this many
+    #[arg(long)]
+    max_anchors: Option<usize>,
+
+    /// Highlight CIGAR insertions and deletions of at least this many bases
+    #[arg(long, value_name = "BASES", default_value_t = 20)]
+    gap_threshold: u32,
+
+    /// Caption of per-chain plots, with {name}, {score}, {ref_id}, {rstart}, {rend}, {strand}, {identity}, {coverage} and {mapq} placeholders
+    #[arg(long, value_parser = TitleTemplate::parse)]
+    title_template: Option<TitleTemplate>,
+
+    /// Write one PNG per read, <read>_contactsheet.png, with a thumbnail of every chain labelled with its score
+    #[arg(long, conflicts_with_all = ["combined", "panels"])]
+    contact_sheet: bool,
+
+    /// Caption per-chain plots with score, reference and span on one line and identity and query coverage on a second
+    #[arg(long, conflicts_with = "title_template")]
+    rich_caption: bool,
+
+    /// Which coordinate goes on the x axis, named x first
+    #[arg(long, value_enum, default_value_t = Axes::RefQuery)]
+    axes: Axes,
+
+    /// Colour chains by whether they were considered, or by score along a gradient
+    #[arg(long, value_enum, default_value_t = ColorBy::Considered)]
+    color_by: ColorBy,
+
+    /// Colour palette of the plotted series
+    #[arg(long, value_enum, default_value_t = ColorPalette::Default)]
+    palette: ColorPalette,
+
+    /// Light or dark plot background
+    #[arg(long, value_enum, default_value_t = Theme::Light)]
+    theme: Theme,
+
+    /// Units of the reference axis labels
+    #[arg(long, value_enum, default_value_t = Units::Auto)]
+    units: Units,
+
+    /// Move anchors whose k-mer runs past the end of the read back onto it before plotting
+    #[arg(long)]
+    clamp_anchors: bool,
+
+    /// Warn about reads whose number of CIGARs differs from their number of chains
+    #[arg(long, conflicts_with = "mapping_only")]
+    chains_without_cigar: bool,
+
+    /// Treat every validation warning as an error and abort on the first read with one
+    #[arg(long)]
+    strict: bool,
+
+    /// Print summary statistics of the parsed chains instead of plotting
+    #[arg(long)]
+    stats: bool,
+
+    /// Compare each chain's score with one recomputed from its anchors in the --stats output
+    #[arg(long, requires = "stats")]
+    verify_score: bool,
+
+    /// List the reads and number of images that would be plotted, without writing any files
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Number of worker threads; 0 or omitted uses every core
+    #[arg(short = 't', long)]
+    threads: Option<usize>,
+
+    /// Which plotting loops run in parallel: across reads, across the chains of a read, or both
+    #[arg(long, value_enum, default_value_t = Parallelism::Read)]
+    parallelism: Parallelism,
+
+    /// Parse and plot reads as they are read instead of loading the whole input
+    #[arg(long, conflicts_with_all = ["tsv", "summary", "anchors_tsv", "bed", "report_cigar_diff", "paf", "extract_ref", "json", "stats", "dry_run"])]
+    stream: bool,
+
+    /// Format of the input file
+    #[arg(long, value_enum, default_value_t = InputFormat::Dump)]
+    input_format: InputFormat,
+
+    /// How skipped malformed blocks are reported on stderr
+    #[arg(long, value_enum, default_value_t = ErrorFormat::Human)]
+    error_format: ErrorFormat,
+
+    /// Image format of the generated plots
+    #[arg(long, value_enum, default_value_t = ImageFormat::Png)]
+    format: ImageFormat,
+
+    /// Plot width in pixels
+    #[arg(long, default_value_t = 1600)]
+    width: u32,
+
+    /// Plot height in pixels
+    #[arg(long, default_value_t = 1600)]
+    height: u32,
+
+    /// Plot size as WIDTHxHEIGHT, overriding --width and --height
+    #[arg(long, value_parser = parse_size, conflicts_with_all = ["width", "height"])]
+    size: Option<(u32, u32)>,
+
+    /// Also write one row per chain to this TSV file
+    #[arg(long, value_hint = ValueHint::FilePath)]
+    tsv: Option<PathBuf>,
+
+    /// Also write one summary row per read to this TSV file
+    #[arg(long, value_hint = ValueHint::FilePath)]
+    summary: Option<PathBuf>,
+
+    /// Also write one row per background and chain anchor to this TSV file
+    #[arg(long, value_hint = ValueHint::FilePath)]
+    anchors_tsv: Option<PathBuf>,
+
+    /// Also write the reference span of every considered chain to this BED file
+    #[arg(long, value_hint = ValueHint::FilePath)]
+    bed: Option<PathBuf>,
+
+    /// Also write the edit distance between each chain's piecewise and SSW CIGARs to this TSV file
+    #[arg(long, value_hint = ValueHint::FilePath, conflicts_with = "mapping_only")]
+    report_cigar_diff: Option<PathBuf>,
+
+    /// Read `=` and `X` as `M` when comparing CIGARs for --report-cigar-diff,
+    /// so only indels and clips count as differences
+    #[arg(long, requires = "report_cigar_diff")]
+    cigar_diff_matches_as_m: bool,
+
+    /// Also write every considered chain as a PAF line, with its CIGAR as a `cg:Z:` tag
+    #[arg(long, value_hint = ValueHint::FilePath)]
+    paf: Option<PathBuf>,
+
+    /// Uncompressed reference FASTA, indexed through its `.fai` when present
+    #[arg(long, value_hint = ValueHint::FilePath)]
+    reference: Option<PathBuf>,
+
+    /// Also write the reference sequence under every considered chain to this FASTA file
+    #[arg(long, value_hint = ValueHint::FilePath, requires = "reference")]
+    extract_ref: Option<PathBuf>,
+
+    /// Tab-separated `<ref_id>\t<name>` table; names replace ids in captions and TSV/BED exports
+    #[arg(long, value_hint = ValueHint::FilePath)]
+    ref_names: Option<String>,
+
+    /// Tab-separated `<ref_id>\t<length>` table; fills PAF target lengths and checks chain spans against it
+    #[arg(long, value_hint = ValueHint::FilePath)]
+    ref_lengths: Option<String>,
+
+    /// Also write the parsed reads, chains and anchors to this JSON file
+    #[arg(long, value_hint = ValueHint::FilePath)]
+    json: Option<PathBuf>,
+
+    /// Indent the JSON output
+    #[arg(long, requires = "json")]
+    pretty: bool,
 }
 
-fn parse_anchors(bytes: &[u8], i: &mut usize) -> Vec<Anchor> {
-    let mut anchors = Vec::new();
-    while bytes[*i] != b']' {
-        *i += 1;
-        let start = *i;
-        while bytes[*i] != b',' {
-            *i += 1;
-        }
-        let ref_start = std::str::from_utf8(&bytes[start..*i])
-            .unwrap()
-            .parse()
-            .unwrap();
-        *i += 1;
-        let start = *i;
-        while bytes[*i] != b'}' {
-            *i += 1;
-        }
-        let query_start = std::str::from_utf8(&bytes[start..*i])
-            .unwrap()
-            .parse()
-            .unwrap();
-        *i += 1;
-
-        anchors.push(Anchor {
-            ref_start,
-            query_start,
-        });
-    }
-    anchors
+#[derive(ValueEnum, Debug, Clone, Copy)]
+enum InputFormat {
+    /// The aligner's chain dump
+    Dump,
+    /// Pairwise mapping format, with CIGARs read from `cg:Z:` tags
+    Paf,
 }
 
-fn parse_chains(bytes: &[u8], i: &mut usize) -> Vec<Chain> {
-    let mut chains = Vec::new();
-    while bytes[*i] != b']' {
-        *i += 8;
-        let start = *i;
-        while bytes[*i] != b',' {
-            *i += 1;
-        }
-        let ref_id = std::str::from_utf8(&bytes[start..*i])
-            .unwrap()
-            .parse()
-            .unwrap();
-        *i += 7;
-        let start = *i;
-        while bytes[*i] != b',' {
-            *i += 1;
-        }
-        let score = std::str::from_utf8(&bytes[start..*i])
-            .unwrap()
-            .parse()
-            .unwrap();
-        *i += 13;
-        let start = *i;
-        while bytes[*i] != b',' {
-            *i += 1;
-        }
-        let query_start = std::str::from_utf8(&bytes[start..*i])
-            .unwrap()
-            .parse()
-            .unwrap();
-        *i += 11;
-        let start = *i;
-        while bytes[*i] != b',' {
-            *i += 1;
-        }
-        let query_end = std::str::from_utf8(&bytes[start..*i])
-            .unwrap()
-            .parse()
-            .unwrap();
-        *i += 11;
-        let start = *i;
-        while bytes[*i] != b',' {
-            *i += 1;
-        }
-        let ref_start = std::str::from_utf8(&bytes[start..*i])
-            .unwrap()
-            .parse()
-            .unwrap();
-        *i += 9;
-        let start = *i;
-        while bytes[*i] != b',' {
-            *i += 1;
-        }
-        let ref_end = std::str::from_utf8(&bytes[start..*i])
-            .unwrap()
-            .parse()
-            .unwrap();
-        *i += 12;
-        let start = *i;
-        while bytes[*i] != b',' {
-            *i += 1;
-        }
-        let is_revcomp = std::str::from_utf8(&bytes[start..*i])
-            .unwrap()
-            .parse()
-            .unwrap();
-        *i += 10;
-        let anchors = parse_anchors(bytes, i);
-        *i += 2;
-
-        chains.push(Chain {
-            ref_id,
-            score,
-            qspan: [query_start, query_end],
-            rspan: [ref_start, ref_end],
-            is_revcomp,
-            anchors,
-            cigar: "".to_owned(),
-            ref_start: 0,
-            considered: false,
-            ssw_cigar: "".to_owned(),
-            ssw_ref_start: 0,
-        });
-    }
-    chains
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum ErrorFormat {
+    /// A warning line per error, at the level set by `-q`/`-v`
+    Human,
+    /// A JSON object per error with its block, byte offset, field and message,
+    /// printed whatever the verbosity
+    Json,
 }
 
-fn parse_cigars(bytes: &[u8], i: &mut usize, chains: &mut [Chain]) {
-    let mut n = 0;
-    while bytes[*i] != b']' {
-        *i += 1;
-        let start = *i;
-        while bytes[*i] != b',' {
-            *i += 1;
-        }
-        let cigar = std::str::from_utf8(&bytes[start..*i])
-            .unwrap()
-            .parse()
-            .unwrap();
-        *i += 16;
-        let considered = bytes[*i] == b'1';
-        *i += 9;
-        let start = *i;
-        while bytes[*i] != b',' {
-            *i += 1;
-        }
-        let ref_start = std::str::from_utf8(&bytes[start..*i])
-            .unwrap()
-            .parse()
-            .unwrap();
-        *i += 5;
-        let start = *i;
-        while bytes[*i] != b',' {
-            *i += 1;
-        }
-        let ssw_cigar = std::str::from_utf8(&bytes[start..*i])
-            .unwrap()
-            .parse()
-            .unwrap();
-        *i += 12;
-        let start = *i;
-        while bytes[*i] != b')' {
-            *i += 1;
+/// A skipped block as printed by `--error-format json`.
+#[derive(Serialize)]
+struct ParseErrorRecord<'a> {
+    block: usize,
+    offset: usize,
+    field: &'a str,
+    message: String,
+}
+
+fn report_parse_error(block: usize, error: &ParseError, format: ErrorFormat) {
+    match format {
+        ErrorFormat::Human => log::warn!("skipping block {block}: {error}"),
+        ErrorFormat::Json => {
+            let record = ParseErrorRecord {
+                block,
+                offset: error.offset,
+                field: error.field,
+                message: error.to_string(),
+            };
+            eprintln!("{}", serde_json::to_string(&record).unwrap());
         }
-        let ssw_ref_start = std::str::from_utf8(&bytes[start..*i])
-            .unwrap()
-            .parse()
-            .unwrap();
-        *i += 1;
-        chains[n].cigar = cigar;
-        chains[n].ref_start = ref_start;
-        chains[n].ssw_cigar = ssw_cigar;
-        chains[n].ssw_ref_start = ssw_ref_start;
-        chains[n].considered = considered;
-        n += 1;
-    }
-}
-
-fn search_until(bytes: &[u8], i: &mut usize, search: &[u8], stop: &[u8]) -> bool {
-    while &bytes[*i..*i + search.len()] != search && &bytes[*i..*i + stop.len()] != stop {
-        *i += 1;
-    }
-    if &bytes[*i..*i + search.len()] == search {
-        *i += search.len();
-        true
-    } else {
-        false
-    }
-}
-
-fn parse_reads(bytes: &[u8], i: &mut usize, mapping_only: bool) -> Option<Read> {
-    *i += 7;
-    let start = *i;
-    while bytes[*i] != b'\n' {
-        *i += 1;
-    }
-    let name = std::str::from_utf8(&bytes[start..*i])
-        .unwrap()
-        .parse()
-        .unwrap();
-    *i += 3;
-    let start = *i;
-    while bytes[*i] != b',' {
-        *i += 1;
-    }
-    let read_len = std::str::from_utf8(&bytes[start..*i])
-        .unwrap()
-        .parse()
-        .unwrap();
-    *i += 3;
-    let start = *i;
-    while bytes[*i] != b'\n' {
-        *i += 1;
-    }
-    let k = std::str::from_utf8(&bytes[start..*i])
-        .unwrap()
-        .parse()
-        .unwrap();
-    *i += 1;
-
-    let mut fwd_anchors = Vec::new();
-    if search_until(
-        bytes,
-        i,
-        b"Anchors for forward strand [",
-        b"Anchors for reverse strand [",
-    ) {
-        fwd_anchors = parse_anchors(bytes, i);
-        *i += 2;
-    }
-
-    let mut rev_anchors = Vec::new();
-    if search_until(bytes, i, b"Anchors for reverse strand [", b"Chains[") {
-        rev_anchors = parse_anchors(bytes, i);
-        *i += 2;
-    }
-
-    let mut chains = Vec::new();
-    if search_until(bytes, i, b"Chains[", b"Done!") {
-        chains = parse_chains(bytes, i);
-        *i += 2;
-    }
-    if chains.is_empty() {
-        return None;
     }
+}
 
-    if mapping_only {
-        for (idx, chain) in chains.iter_mut().enumerate() {
-            chain.considered = idx == 0;
-        }
-    } else {
-        *i += 8;
-        parse_cigars(bytes, i, &mut chains);
+fn parse_size(s: &str) -> Result<(u32, u32), String> {
+    let (width, height) = s
+        .split_once(['x', 'X'])
+        .ok_or_else(|| format!("expected WIDTHxHEIGHT, got {s:?}"))?;
+    let parse = |v: &str| {
+        v.trim()
+            .parse::<u32>()
+            .ok()
+            .filter(|v| *v > 0)
+            .ok_or_else(|| format!("invalid dimension {v:?}"))
+    };
+    Ok((parse(width)?, parse(height)?))
+}
+
+fn parse_ref_window(s: &str) -> Result<(u32, u32), String> {
+    let (start, end) = s
+        .split_once(':')
+        .ok_or_else(|| format!("expected START:END, got {s:?}"))?;
+    let parse = |v: &str| {
+        v.trim()
+            .parse::<u32>()
+            .map_err(|_| format!("invalid position {v:?}"))
+    };
+    let (start, end) = (parse(start)?, parse(end)?);
+    if end <= start {
+        return Err(format!("window end {end} must be after its start {start}"));
     }
+    Ok((start, end))
+}
 
-    Some(Read {
-        name,
-        read_len,
-        k,
-        fwd_anchors,
-        rev_anchors,
-        chains,
+fn parse_anchor(s: &str) -> Result<Anchor, String> {
+    let (ref_start, query_start) = s
+        .split_once(':')
+        .ok_or_else(|| format!("expected REF:QUERY, got {s:?}"))?;
+    let parse = |v: &str| {
+        v.trim()
+            .parse::<u32>()
+            .map_err(|_| format!("invalid position {v:?}"))
+    };
+    Ok(Anchor {
+        ref_start: parse(ref_start)?,
+        query_start: parse(query_start)?,
     })
 }
 
-fn parse_file(f: &str, n: Option<usize>, mapping_only: bool) -> Vec<Read> {
-    let bytes = f.as_bytes();
-    let mut reads = Vec::new();
-
-    let mut i = 0;
+fn parse_fraction(s: &str) -> Result<f64, String> {
+    s.trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|v| (0.0..=1.0).contains(v))
+        .ok_or_else(|| format!("expected a fraction between 0 and 1, got {s:?}"))
+}
 
-    while i + 7 < bytes.len() {
-        if &bytes[i..i + 7] == b"Query: " {
-            if let Some(read) = parse_reads(bytes, &mut i, mapping_only) {
-                reads.push(read);
-                if let Some(max) = n {
-                    if reads.len() >= max {
-                        break;
-                    }
-                }
-            }
-        } else {
-            i += 1;
-        }
+fn check_fraction(v: f64) -> Result<f64, String> {
+    if (0.0..=1.0).contains(&v) {
+        Ok(v)
+    } else {
+        Err(format!("expected a fraction between 0 and 1, got {v}"))
     }
+}
 
-    println!("parsed {} reads", reads.len());
-    reads
+/// Option defaults loaded from `--config`. Each key is the name of an `Args`
+/// field and takes the same values as its option, so enums are spelled as on
+/// the command line and `size = "800x600"`.
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct Config {
+    n: Option<usize>,
+    output: Option<String>,
+    mapping_only: Option<bool>,
+    name_regex: Option<String>,
+    reads_file: Option<String>,
+    min_read_len: Option<u32>,
+    max_read_len: Option<u32>,
+    sample: Option<NonZeroUsize>,
+    min_score: Option<f64>,
+    ref_ids: Option<Vec<u32>>,
+    min_divergence: Option<f64>,
+    min_query_coverage: Option<f64>,
+    considered_only: Option<bool>,
+    max_chains_per_read: Option<usize>,
+    report_overlaps: Option<f64>,
+    #[serde(default, deserialize_with = "value_enum")]
+    sort_chains: Option<ChainOrder>,
+    verbose: Option<u8>,
+    quiet: Option<bool>,
+    mark_cigar_mismatch: Option<bool>,
+    combined: Option<bool>,
+    diff: Option<bool>,
+    panels: Option<usize>,
+    group_by_ref: Option<bool>,
+    scale_bar: Option<bool>,
+    ref_window: Option<String>,
+    coverage_track: Option<bool>,
+    highlight_anchor: Option<String>,
+    highlight_tolerance: Option<u32>,
+    diagonal: Option<bool>,
+    #[serde(default, deserialize_with = "value_enum")]
+    anchor_style: Option<AnchorStyle>,
+    max_anchors: Option<usize>,
+    gap_threshold: Option<u32>,
+    title_template: Option<String>,
+    rich_caption: Option<bool>,
+    contact_sheet: Option<bool>,
+    #[serde(default, deserialize_with = "value_enum")]
+    axes: Option<Axes>,
+    #[serde(default, deserialize_with = "value_enum")]
+    color_by: Option<ColorBy>,
+    #[serde(default, deserialize_with = "value_enum")]
+    palette: Option<ColorPalette>,
+    #[serde(default, deserialize_with = "value_enum")]
+    theme: Option<Theme>,
+    #[serde(default, deserialize_with = "value_enum")]
+    units: Option<Units>,
+    clamp_anchors: Option<bool>,
+    chains_without_cigar: Option<bool>,
+    strict: Option<bool>,
+    stats: Option<bool>,
+    verify_score: Option<bool>,
+    dry_run: Option<bool>,
+    threads: Option<usize>,
+    #[serde(default, deserialize_with = "value_enum")]
+    parallelism: Option<Parallelism>,
+    stream: Option<bool>,
+    #[serde(default, deserialize_with = "value_enum")]
+    input_format: Option<InputFormat>,
+    #[serde(default, deserialize_with = "value_enum")]
+    error_format: Option<ErrorFormat>,
+    #[serde(default, deserialize_with = "value_enum")]
+    format: Option<ImageFormat>,
+    width: Option<u32>,
+    height: Option<u32>,
+    size: Option<String>,
+    tsv: Option<PathBuf>,
+    summary: Option<PathBuf>,
+    anchors_tsv: Option<PathBuf>,
+    bed: Option<PathBuf>,
+    report_cigar_diff: Option<PathBuf>,
+    cigar_diff_matches_as_m: Option<bool>,
+    paf: Option<PathBuf>,
+    reference: Option<PathBuf>,
+    extract_ref: Option<PathBuf>,
+    ref_names: Option<String>,
+    ref_lengths: Option<String>,
+    json: Option<PathBuf>,
+    pretty: Option<bool>,
 }
 
-fn sanitize_filename<S: AsRef<str>>(name: S) -> String {
-    name.as_ref()
-        .chars()
-        .map(|c| {
-            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
-                c
-            } else {
-                '_'
-            }
-        })
-        .collect()
+/// Reads an option enum by its command-line name.
+fn value_enum<'de, D: Deserializer<'de>, T: ValueEnum>(d: D) -> Result<Option<T>, D::Error> {
+    let name = String::deserialize(d)?;
+    T::from_str(&name, false)
+        .map(Some)
+        .map_err(de::Error::custom)
 }
 
-fn plot_reads(reads: Vec<Read>, output: &str, mapping_only: bool) {
-    create_dir_all(output).unwrap();
+impl Config {
+    fn load(path: &Path) -> Result<Config, String> {
+        let text = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        toml::from_str(&text).map_err(|e| e.to_string())
+    }
 
-    let total_chains: u64 = reads.iter().map(|r| r.chains.len() as u64).sum();
+    /// Fills in every option of `args` that `matches` did not get from the
+    /// command line, either directly or through an option conflicting with
+    /// it, so `--size` on the command line also overrides a file's `width`.
+    fn apply(self, args: &mut Args, matches: &ArgMatches) -> Result<(), String> {
+        let command = Args::command();
+        let on_command_line: Vec<&Arg> = command
+            .get_arguments()
+            .filter(|arg| {
+                matches.value_source(arg.get_id().as_str()) == Some(ValueSource::CommandLine)
+            })
+            .collect();
+        let conflict = |arg: &Arg, id: &str| {
+            command
+                .get_arg_conflicts_with(arg)
+                .iter()
+                .any(|other| other.get_id() == id)
+        };
+        let overridden = |id: &str| {
+            on_command_line.iter().any(|arg| {
+                arg.get_id() == id
+                    || conflict(arg, id)
+                    || command
+                        .get_arguments()
+                        .find(|own| own.get_id() == id)
+                        .is_some_and(|own| conflict(own, arg.get_id().as_str()))
+            })
+        };
+
+        macro_rules! merge {
+            ($($field:ident),* $(,)?) => {$(
+                if let Some(value) = self.$field
+                    && !overridden(stringify!($field))
+                {
+                    args.$field = value.into();
+                }
+            )*};
+            ($($field:ident with $parse:expr),* $(,)?) => {$(
+                if let Some(value) = self.$field
+                    && !overridden(stringify!($field))
+                {
+                    let parsed = $parse(&value)
+                        .map_err(|e| format!("{}: {e}", stringify!($field)))?;
+                    args.$field = parsed.into();
+                }
+            )*};
+        }
 
-    let pb = ProgressBar::new(total_chains);
-    pb.set_style(
-        ProgressStyle::with_template(
-            "[{elapsed_precise}] {bar:60} {pos}/{len} chains plotted ({eta})",
-        )
-        .unwrap(),
-    );
+        merge!(
+            n,
+            output,
+            mapping_only,
+            reads_file,
+            min_read_len,
+            max_read_len,
+            sample,
+            min_score,
+            ref_ids,
+            min_divergence,
+            considered_only,
+            max_chains_per_read,
+            sort_chains,
+            verbose,
+            quiet,
+            mark_cigar_mismatch,
+            combined,
+            diff,
+            panels,
+            group_by_ref,
+            scale_bar,
+            coverage_track,
+            highlight_tolerance,
+            rich_caption,
+            contact_sheet,
+            diagonal,
+            anchor_style,
+            max_anchors,
+            gap_threshold,
+            axes,
+            color_by,
+            palette,
+            theme,
+            units,
+            clamp_anchors,
+            chains_without_cigar,
+            strict,
+            stats,
+            verify_score,
+            dry_run,
+            threads,
+            parallelism,
+            stream,
+            input_format,
+            error_format,
+            format,
+            width,
+            height,
+            tsv,
+            summary,
+            anchors_tsv,
+            bed,
+            report_cigar_diff,
+            cigar_diff_matches_as_m,
+            paf,
+            reference,
+            extract_ref,
+            ref_names,
+            ref_lengths,
+            json,
+            pretty,
+        );
+        merge!(
+            min_query_coverage with |fraction: &f64| check_fraction(*fraction),
+            report_overlaps with |fraction: &f64| check_fraction(*fraction),
+            ref_window with |window: &String| parse_ref_window(window),
+            name_regex with |pattern: &String| Regex::new(pattern),
+            title_template with |template: &String| TitleTemplate::parse(template),
+            size with |size: &String| parse_size(size),
+            highlight_anchor with |anchor: &String| parse_anchor(anchor),
+        );
+        Ok(())
+    }
+}
 
-    reads.par_iter().for_each(|read| {
-        let safe_name = sanitize_filename(&read.name);
-        let read_dir = Path::new(output).join(safe_name);
-        create_dir_all(&read_dir).unwrap();
-
-        read.chains
-            .par_iter()
-            .enumerate()
-            .for_each(|(chain_idx, chain)| {
-                plot_chain(read, chain, chain_idx, &read_dir, mapping_only);
-                pb.inc(1);
+/// Parses the command line over the defaults of `--config`, exiting with a
+/// usage error when the file cannot be used.
+fn parse_args() -> Args {
+    let matches = Args::command().get_matches();
+    let mut args = Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    if let Some(path) = args.config.clone() {
+        Config::load(&path)
+            .and_then(|config| config.apply(&mut args, &matches))
+            .unwrap_or_else(|e| {
+                Args::command()
+                    .error(
+                        ErrorKind::InvalidValue,
+                        format!("config {}: {e}", path.display()),
+                    )
+                    .exit()
             });
-    });
+    }
+    args
+}
 
-    pb.finish();
+/// Logs to stderr at the level of `-q`/`-v` unless `RUST_LOG` sets one, with
+/// warnings and errors prefixed like the rest of the output.
+fn init_logger(verbosity: Verbosity) {
+    env_logger::Builder::new()
+        .filter_level(verbosity.level())
+        .parse_default_env()
+        .format(|out, record| match record.level() {
+            Level::Error => writeln!(out, "error: {}", record.args()),
+            Level::Warn => writeln!(out, "warning: {}", record.args()),
+            _ => writeln!(out, "{}", record.args()),
+        })
+        .init();
 }
 
-fn parse_cigar_to_path(cigar: &str, ref_start: u32) -> Vec<(u32, u32)> {
-    let mut path = Vec::new();
-    let mut ref_pos = ref_start;
-    let mut query_pos = 0u32;
+fn main() -> io::Result<()> {
+    let args = parse_args();
+    init_logger(Verbosity::from_flags(args.quiet, args.verbose));
+    let pool = ThreadPoolBuilder::new()
+        .num_threads(args.threads.unwrap_or(0))
+        .build()
+        .map_err(io::Error::other)?;
+    pool.install(|| run_inputs(&args))
+}
 
-    path.push((ref_pos, query_pos));
+/// Runs every input in turn. A single input plots straight into the output
+/// directory; several each get a subdirectory named after the input file, so
+/// reads of the same name end up side by side.
+fn run_inputs(args: &Args) -> io::Result<()> {
+    if let [input] = args.file.as_slice()
+        && !args.diff
+    {
+        return run(args, input, &args.output);
+    }
+    let exports = [
+        ("--tsv", args.tsv.is_some()),
+        ("--summary", args.summary.is_some()),
+        ("--anchors-tsv", args.anchors_tsv.is_some()),
+        ("--bed", args.bed.is_some()),
+        ("--report-cigar-diff", args.report_cigar_diff.is_some()),
+        ("--paf", args.paf.is_some()),
+        ("--extract-ref", args.extract_ref.is_some()),
+        ("--json", args.json.is_some()),
+    ];
+    if let Some((option, _)) = exports.iter().find(|(_, set)| *set) {
+        Args::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                format!("{option} writes a single file and takes only one input"),
+            )
+            .exit();
+    }
 
-    let mut i = 0;
-    let chars: Vec<char> = cigar.chars().collect();
+    let mut input_dirs = ReadDirs::default();
+    let names: Vec<String> = args
+        .file
+        .iter()
+        .map(|input| {
+            let name = match input.as_str() {
+                "-" => "stdin".into(),
+                path => Path::new(path)
+                    .file_stem()
+                    .map_or_else(|| path.into(), |stem| stem.to_string_lossy()),
+            };
+            input_dirs.dir_for(&name)
+        })
+        .collect();
+    if args.diff {
+        return run_diff(args, &names);
+    }
+    for (input, name) in args.file.iter().zip(&names) {
+        let output = Path::new(&args.output).join(name);
+        log::info!("plotting {input} into {}", output.display());
+        run(args, input, &output.to_string_lossy())?;
+    }
+    Ok(())
+}
 
-    while i < chars.len() {
-        let mut num_str = String::new();
-        while i < chars.len() && chars[i].is_ascii_digit() {
-            num_str.push(chars[i]);
-            i += 1;
-        }
+/// Overlays the chains of the two inputs read by read, labelling each input
+/// by its name in `names`.
+fn run_diff(args: &Args, names: &[String]) -> io::Result<()> {
+    let [a, b] = args.file.as_slice() else {
+        Args::command()
+            .error(
+                ErrorKind::WrongNumberOfValues,
+                format!("--diff takes exactly two inputs, got {}", args.file.len()),
+            )
+            .exit();
+    };
+    let read_filter = read_filter(args)?;
+    let filter = chain_filter(args);
+    let mut options = plot_options(args)?;
+    let reads_a = load_reads(
+        args,
+        a,
+        read_filter.clone(),
+        &filter,
+        &mut options.ref_names,
+    )?;
+    let reads_b = load_reads(args, b, read_filter, &filter, &mut options.ref_names)?;
+
+    let written = plot_diff(
+        [&reads_a, &reads_b],
+        [&names[0], &names[1]],
+        &args.output,
+        &options,
+    );
+    log::info!("wrote {} diff plots under {}", written.len(), args.output);
+    Ok(())
+}
 
-        if i >= chars.len() {
-            break;
+fn run(args: &Args, input: &str, output: &str) -> io::Result<()> {
+    let read_filter = read_filter(args)?;
+    let filter = chain_filter(args);
+    let mut options = plot_options(args)?;
+
+    if args.stream {
+        if !matches!(args.input_format, InputFormat::Dump) {
+            Args::command()
+                .error(
+                    ErrorKind::ArgumentConflict,
+                    "--stream only supports the dump input format",
+                )
+                .exit();
         }
+        return stream_reads(args, input, output, read_filter, &filter, &options);
+    }
 
-        let count: u32 = num_str.parse().unwrap_or(0);
-        let operation = chars[i];
-        i += 1;
+    let reads = load_reads(args, input, read_filter, &filter, &mut options.ref_names)?;
 
-        match operation {
-            'M' | '=' | 'X' => {
-                ref_pos += count;
-                query_pos += count;
-                path.push((ref_pos, query_pos));
-            }
-            'I' => {
-                query_pos += count;
-                path.push((ref_pos, query_pos));
-            }
-            'D' => {
-                ref_pos += count;
-                path.push((ref_pos, query_pos));
-            }
-            'S' => {
-                query_pos += count;
-                path.push((ref_pos, query_pos));
-            }
-            'N' => {
-                ref_pos += count;
-                path.push((ref_pos, query_pos));
-            }
-            _ => {}
+    if args.dry_run {
+        dry_run(&reads, output, &options);
+        return Ok(());
+    }
+
+    let provenance = provenance(args, input);
+    if let Some(path) = &args.tsv {
+        write_chains_tsv(
+            BufWriter::new(File::create(path)?),
+            &reads,
+            &options.ref_names,
+            &provenance,
+        )?;
+    }
+    if let Some(path) = &args.summary {
+        write_read_summary_tsv(BufWriter::new(File::create(path)?), &reads, &provenance)?;
+    }
+    if let Some(path) = &args.anchors_tsv {
+        write_anchors_tsv(BufWriter::new(File::create(path)?), &reads, &provenance)?;
+    }
+    if let Some(path) = &args.bed {
+        write_bed(
+            BufWriter::new(File::create(path)?),
+            &reads,
+            &options.ref_names,
+            &provenance,
+        )?;
+    }
+    if let Some(path) = &args.report_cigar_diff {
+        write_cigar_diff_tsv(
+            BufWriter::new(File::create(path)?),
+            &reads,
+            args.cigar_diff_matches_as_m,
+            &provenance,
+        )?;
+    }
+    if let Some(path) = &args.paf {
+        write_paf(
+            BufWriter::new(File::create(path)?),
+            &reads,
+            &options.ref_names,
+        )?;
+    }
+    if let Some(path) = &args.extract_ref {
+        let reference = args
+            .reference
+            .as_deref()
+            .expect("--extract-ref requires --reference");
+        write_ref_fasta(
+            BufWriter::new(File::create(path)?),
+            &reads,
+            &options.ref_names,
+            &mut IndexedFasta::open(reference)?,
+        )?;
+    }
+    if let Some(path) = &args.json {
+        write_json(
+            BufWriter::new(File::create(path)?),
+            &reads,
+            args.pretty,
+            &provenance,
+        )?;
+    }
+    if args.stats {
+        let mut stats = Stats::compute(&reads);
+        if args.verify_score {
+            stats.score_check = ScoreCheck::compute(&reads);
         }
+        println!("{stats}");
+        return Ok(());
     }
+    let plotted = plot_reads(reads, output, &options);
+    write_manifest_file(output, &plotted)
+}
 
-    path
+fn read_filter(args: &Args) -> io::Result<ReadFilter> {
+    Ok(ReadFilter {
+        name_regex: args.name_regex.clone(),
+        names: match &args.reads_file {
+            Some(path) => Some(parse_read_names(&read_input(path)?)),
+            None => None,
+        },
+        min_read_len: args.min_read_len,
+        max_read_len: args.max_read_len,
+        sample: args.sample,
+    })
 }
 
-fn plot_chain(read: &Read, chain: &Chain, chain_idx: usize, read_dir: &Path, mapping_only: bool) {
-    let ref_start = chain.rspan[0];
-    let ref_end = chain.rspan[1];
-    let padding = read.read_len / 2;
+fn chain_filter(args: &Args) -> ChainFilter {
+    ChainFilter {
+        min_score: args.min_score,
+        ref_ids: args.ref_ids.clone(),
+        min_divergence: args.min_divergence,
+        min_query_coverage: args.min_query_coverage,
+        considered_only: args.considered_only,
+        max_chains: args.max_chains_per_read,
+    }
+}
 
-    let (ref_plot_start, ref_plot_end) = if mapping_only {
-        let ref_plot_start = ref_start.saturating_sub(padding);
-        let ref_plot_end = ref_end + padding;
-        (ref_plot_start, ref_plot_end)
-    } else {
-        let min_ref_start = ref_start.min(chain.ssw_ref_start);
-        let max_ref_start = ref_start.max(chain.ssw_ref_start);
-        let ref_plot_start = min_ref_start.saturating_sub(padding);
-        let ref_plot_end = max_ref_start + padding + read.read_len;
-        (ref_plot_start, ref_plot_end)
+fn plot_options(args: &Args) -> io::Result<PlotOptions> {
+    let mut ref_names = match &args.ref_names {
+        Some(path) => RefNames::load(path)?,
+        None => RefNames::default(),
     };
+    if let Some(path) = &args.ref_lengths {
+        ref_names.load_lengths(path)?;
+    }
+    Ok(PlotOptions {
+        mapping_only: args.mapping_only,
+        format: args.format,
+        size: args.size.unwrap_or((args.width, args.height)),
+        verbosity: Verbosity::from_flags(args.quiet, args.verbose),
+        mark_cigar_mismatch: args.mark_cigar_mismatch,
+        combined: args.combined,
+        diagonal: args.diagonal,
+        units: args.units,
+        colors: args.palette.colors(),
+        theme: args.theme,
+        anchor_style: args.anchor_style,
+        max_anchors: args.max_anchors,
+        ref_names,
+        gap_threshold: args.gap_threshold,
+        title_template: args.title_template.clone(),
+        rich_caption: args.rich_caption,
+        contact_sheet: args.contact_sheet,
+        panels: args.panels,
+        group_by_ref: args.group_by_ref,
+        scale_bar: args.scale_bar,
+        color_by: args.color_by,
+        ref_window: args.ref_window,
+        axes: args.axes,
+        coverage_track: args.coverage_track,
+        highlight_anchor: args.highlight_anchor.clone(),
+        highlight_tolerance: args.highlight_tolerance,
+        parallelism: args.parallelism,
+    })
+}
 
-    let filename = format!("chain_id={}_score={:.2}.png", chain_idx, chain.score);
-    let filepath = read_dir.join(filename.clone());
-
-    let root = BitMapBackend::new(&filepath, (1600, 1600)).into_drawing_area();
-    root.fill(&WHITE).unwrap();
-
-    let title = format!(
-        "Score: {:.2}, Ref ID: {}, Ref Span: {}-{}",
-        chain.score, chain.ref_id, ref_start, ref_end
-    );
-
-    let mut chart = ChartBuilder::on(&root)
-        .caption(&title, ("Arial", 20))
-        .margin(50)
-        .x_label_area_size(60)
-        .y_label_area_size(40)
-        .build_cartesian_2d(ref_plot_start..ref_plot_end, 0u32..read.read_len)
-        .unwrap();
-
-    chart
-        .configure_mesh()
-        .x_desc("Reference")
-        .y_desc("Query")
-        .draw()
-        .unwrap();
-
-    let anchors_to_plot = if chain.is_revcomp {
-        &read.rev_anchors
+/// Logs how many reads were parsed, as a warning when malformed blocks were
+/// skipped so it shows at the default verbosity.
+fn report_parse_summary(parsed: usize, skipped: usize) {
+    let level = if skipped > 0 {
+        Level::Warn
     } else {
-        &read.fwd_anchors
+        Level::Info
     };
+    log::log!(
+        level,
+        "parsed {parsed} reads, skipped {skipped} malformed blocks"
+    );
+}
 
-    let filtered_anchors: Vec<&Anchor> = anchors_to_plot
-        .iter()
-        .filter(|anchor| {
-            anchor.ref_start >= ref_plot_start && anchor.ref_start + read.k <= ref_plot_end
-        })
-        .collect();
-
-    for anchor in &filtered_anchors {
-        let query_end = anchor.query_start + read.k;
-        let ref_end = anchor.ref_start + read.k;
-
-        chart
-            .draw_series(LineSeries::new(
-                vec![(anchor.ref_start, anchor.query_start), (ref_end, query_end)],
-                BLUE.stroke_width(2),
-            ))
-            .unwrap();
-
-        chart
-            .draw_series(PointSeries::of_element(
-                vec![(anchor.ref_start, anchor.query_start)],
-                10,
-                &BLUE,
-                &|c, s, st| Cross::new(c, s, st.filled()),
-            ))
-            .unwrap();
-
-        chart
-            .draw_series(PointSeries::of_element(
-                vec![(ref_end, query_end)],
-                10,
-                &BLUE,
-                &|c, s, st| Cross::new(c, s, st.filled()),
-            ))
-            .unwrap();
-    }
-
-    let chain_color = if chain.considered {
-        GREEN.mix(0.5)
-    } else {
-        RED.mix(0.5)
+/// Parses an input and applies every per-read check, fix-up and filter,
+/// logging what was skipped and dropped.
+fn load_reads(
+    args: &Args,
+    input: &str,
+    read_filter: ReadFilter,
+    filter: &ChainFilter,
+    ref_names: &mut RefNames,
+) -> io::Result<Vec<Read>> {
+    let (mut reads, errors) = match args.input_format {
+        // Only a prefix is needed, so stop reading once `-n` reads are parsed
+        // instead of loading the whole input.
+        InputFormat::Dump if args.n.is_some() => {
+            ReadStream::new(open_input(input)?, args.n, args.mapping_only, read_filter)
+                .collect_reads()?
+        }
+        InputFormat::Dump => {
+            parse_reader(open_input(input)?, args.n, args.mapping_only, &read_filter)?
+        }
+        InputFormat::Paf => parse_paf(
+            read_input(input)?.as_bytes(),
+            args.n,
+            &read_filter,
+            ref_names,
+        ),
     };
+    for (block_idx, e) in &errors {
+        report_parse_error(*block_idx, e, args.error_format);
+    }
+    report_parse_summary(reads.len(), errors.len());
+    if reads.is_empty() && !errors.is_empty() {
+        process::exit(1);
+    }
+    for read in &mut reads {
+        if let Some(order) = args.sort_chains {
+            order.sort(read, args.mapping_only);
+        }
+        report_warnings(read, args, ref_names);
+        if args.clamp_anchors {
+            read.clamp_anchors();
+        }
+    }
 
-    for anchor in &chain.anchors {
-        let query_end = anchor.query_start + read.k;
-        let ref_end = anchor.ref_start + read.k;
-
-        chart
-            .draw_series(LineSeries::new(
-                vec![(anchor.ref_start, anchor.query_start), (ref_end, query_end)],
-                chain_color.stroke_width(4),
-            ))
-            .unwrap();
-    }
-
-    for i in 0..chain.anchors.len().saturating_sub(1) {
-        let current_anchor = &chain.anchors[i];
-        let next_anchor = &chain.anchors[i + 1];
-
-        let current_end_query = current_anchor.query_start + read.k;
-        let current_end_ref = current_anchor.ref_start + read.k;
-
-        chart
-            .draw_series(LineSeries::new(
-                vec![
-                    (current_end_ref, current_end_query),
-                    (next_anchor.ref_start, next_anchor.query_start),
-                ],
-                chain_color.stroke_width(4),
-            ))
-            .unwrap();
-    }
-
-    if !mapping_only {
-        let piecewise_path = parse_cigar_to_path(&chain.cigar, chain.ref_start);
-        if piecewise_path.len() > 1 {
-            chart
-                .draw_series(LineSeries::new(
-                    piecewise_path.clone(),
-                    PURPLE.mix(0.5).stroke_width(4),
-                ))
-                .unwrap();
+    let dropped = filter.apply(&mut reads);
+    if let Some(min_fraction) = args.report_overlaps {
+        for read in &reads {
+            report_overlaps(read, min_fraction);
         }
+    }
+    let mut highlighted = false;
+    for read in &reads {
+        highlighted |= report_highlighted_anchor(read, args);
+    }
+    if !highlighted {
+        report_missing_highlight(args);
+    }
+    if dropped > 0 {
+        log::info!(
+            "filtered out {dropped} chains, {} reads remaining",
+            reads.len()
+        );
+    }
+    Ok(reads)
+}
 
-        let ssw_path = parse_cigar_to_path(&chain.ssw_cigar, chain.ssw_ref_start);
-        if ssw_path.len() > 1 {
-            chart
-                .draw_series(LineSeries::new(
-                    ssw_path.clone(),
-                    ORANGE.mix(0.5).stroke_width(4),
-                ))
-                .unwrap();
+/// Records the version, input and every filter option that was set.
+fn provenance(args: &Args, input: &str) -> Provenance {
+    let mut filters = BTreeMap::new();
+    let mut set = |option, value: Option<String>| {
+        if let Some(value) = value {
+            filters.insert(option, value);
         }
+    };
+    set("n", args.n.map(|n| n.to_string()));
+    set("mapping-only", args.mapping_only.then(|| "true".to_owned()));
+    set("name-regex", args.name_regex.as_ref().map(Regex::to_string));
+    set("reads-file", args.reads_file.clone());
+    set("min-read-len", args.min_read_len.map(|len| len.to_string()));
+    set("max-read-len", args.max_read_len.map(|len| len.to_string()));
+    set("sample", args.sample.map(|k| k.to_string()));
+    set("min-score", args.min_score.map(|score| score.to_string()));
+    set(
+        "ref-id",
+        (!args.ref_ids.is_empty()).then(|| {
+            args.ref_ids
+                .iter()
+                .map(u32::to_string)
+                .collect::<Vec<_>>()
+                .join(",")
+        }),
+    );
+    set("min-divergence", args.min_divergence.map(|d| d.to_string()));
+    set(
+        "min-query-coverage",
+        args.min_query_coverage.map(|c| c.to_string()),
+    );
+    set(
+        "considered-only",
+        args.considered_only.then(|| "true".to_owned()),
+    );
+    set(
+        "max-chains-per-read",
+        args.max_chains_per_read.map(|max| max.to_string()),
+    );
+    Provenance {
+        version: env!("CARGO_PKG_VERSION"),
+        input: input.to_owned(),
+        filters,
     }
+}
 
-    chart
-        .draw_series(std::iter::once(PathElement::new(
-            [(ref_plot_start, 0), (ref_plot_start + 1, 0)],
-            BLUE,
-        )))
-        .unwrap()
-        .label("Blue: Background anchors")
-        .legend(|(x, y)| PathElement::new([(x, y), (x + 20, y)], BLUE));
+/// Writes `manifest.json` into the output directory.
+fn write_manifest_file(output: &str, plotted: &[PlottedRead]) -> io::Result<()> {
+    let path = Path::new(output).join("manifest.json");
+    write_manifest(BufWriter::new(File::create(path)?), plotted)
+}
 
-    let chain_label = format!(
-        "{}: Chain (considered: {})",
-        if chain.considered { "Green" } else { "Red" },
-        chain.considered
+/// Lists what would be plotted without writing anything.
+fn dry_run(reads: &[Read], output: &str, options: &PlotOptions) {
+    let mut images = 0;
+    for read in reads {
+        let read_images = planned_images(read, options);
+        images += read_images;
+        println!(
+            "{}\t{} chains\t{read_images} images",
+            read.name,
+            read.chains.len()
+        );
+    }
+    println!(
+        "would write {images} images for {} reads under {output}",
+        reads.len()
     );
+}
 
-    chart
-        .draw_series(std::iter::once(PathElement::new(
-            [(ref_plot_start, 0), (ref_plot_start + 1, 0)],
-            chain_color,
-        )))
-        .unwrap()
-        .label(&chain_label)
-        .legend(move |(x, y)| PathElement::new([(x, y), (x + 30, y)], chain_color.stroke_width(4)));
-
-    if !mapping_only {
-        let ssw_label = format!("Orange: SSW path:         {}", chain.ssw_cigar);
-        chart
-            .draw_series(std::iter::once(PathElement::new(
-                [(ref_plot_start, 0), (ref_plot_start + 1, 0)],
-                ORANGE.mix(0.5),
-            )))
-            .unwrap()
-            .label(&ssw_label)
-            .legend(|(x, y)| {
-                PathElement::new([(x, y), (x + 30, y)], ORANGE.mix(0.5).stroke_width(4))
-            });
+/// Logs the validation warnings of a read. With `--strict` they are logged as
+/// errors instead and the process exits, so no output is written past the
+/// first inconsistent read.
+fn report_warnings(read: &Read, args: &Args, ref_names: &RefNames) {
+    let cigar_count = args.chains_without_cigar || (args.strict && !args.mapping_only);
+    let warnings = check_all(read, ref_names, cigar_count);
+    if !args.strict {
+        for warning in &warnings {
+            log::warn!("{warning}");
+        }
+        return;
+    }
+    if warnings.is_empty() {
+        return;
+    }
+    for warning in &warnings {
+        log::error!("{warning}");
+    }
+    log::error!(
+        "aborting: --strict is set and read {} has {} validation warning(s)",
+        read.name,
+        warnings.len()
+    );
+    process::exit(1);
+}
 
-        let piecewise_label = format!("Purple: Piecewise path:   {}", chain.cigar);
-        chart
-            .draw_series(std::iter::once(PathElement::new(
-                [(ref_plot_start, 0), (ref_plot_start + 1, 0)],
-                PURPLE.mix(0.5),
-            )))
-            .unwrap()
-            .label(&piecewise_label)
-            .legend(|(x, y)| {
-                PathElement::new([(x, y), (x + 30, y)], PURPLE.mix(0.5).stroke_width(4))
-            });
+/// Notes which chains of a read have an anchor near `--highlight-anchor`.
+/// Returns whether any anchor of the read is near it.
+fn report_highlighted_anchor(read: &Read, args: &Args) -> bool {
+    let Some(target) = &args.highlight_anchor else {
+        return false;
+    };
+    let Some(chains) = read.find_anchor(target, args.highlight_tolerance) else {
+        return false;
+    };
+    let position = format!("{}:{}", target.ref_start, target.query_start);
+    if chains.is_empty() {
+        log::warn!(
+            "read {} has an anchor near {position} outside its chains",
+            read.name
+        );
+    } else {
+        let chains: Vec<String> = chains.iter().map(usize::to_string).collect();
+        log::warn!(
+            "read {} has an anchor near {position} in chains {}",
+            read.name,
+            chains.join(", ")
+        );
     }
+    true
+}
 
-    chart
-        .configure_series_labels()
-        .background_style(WHITE.mix(0.9))
-        .border_style(BLACK)
-        .label_font(("Arial", 22))
-        .position(SeriesLabelPosition::UpperLeft)
-        .draw()
-        .unwrap();
+/// Notes that `--highlight-anchor` matched no anchor of any read.
+fn report_missing_highlight(args: &Args) {
+    if let Some(target) = &args.highlight_anchor {
+        log::warn!(
+            "no anchor within {} bases of {}:{}",
+            args.highlight_tolerance,
+            target.ref_start,
+            target.query_start
+        );
+    }
+}
 
-    root.present().unwrap();
+fn report_overlaps(read: &Read, min_fraction: f64) {
+    for overlap in find_overlaps(read, min_fraction) {
+        log::warn!("{overlap}");
+    }
 }
 
-fn main() -> Result<()> {
-    let args = Args::parse();
-    let file = read_to_string(args.file)?;
-    let reads = parse_file(&file, args.n, args.mapping_only);
-    plot_reads(reads, &args.output, args.mapping_only);
-    Ok(())
+/// Plots reads while they are parsed, for inputs too large to hold in memory.
+fn stream_reads(
+    args: &Args,
+    input: &str,
+    output: &str,
+    read_filter: ReadFilter,
+    filter: &ChainFilter,
+    options: &PlotOptions,
+) -> io::Result<()> {
+    let mut parsed = 0;
+    let mut skipped = 0;
+    let mut dropped = 0;
+    let mut highlighted = false;
+    let mut io_error = None;
+
+    let reads = ReadStream::new(open_input(input)?, args.n, args.mapping_only, read_filter)
+        .map_while(|item| match item {
+            Ok(read) => Some(Some(read)),
+            Err(StreamError::Parse { block, error }) => {
+                report_parse_error(block, &error, args.error_format);
+                skipped += 1;
+                Some(None)
+            }
+            Err(StreamError::Io(e)) => {
+                io_error = Some(e);
+                None
+            }
+        })
+        .flatten()
+        .filter_map(|mut read| {
+            parsed += 1;
+            if let Some(order) = args.sort_chains {
+                order.sort(&mut read, args.mapping_only);
+            }
+            report_warnings(&read, args, &options.ref_names);
+            if args.clamp_anchors {
+                read.clamp_anchors();
+            }
+            dropped += filter.apply_to(&mut read);
+            if let Some(min_fraction) = args.report_overlaps {
+                report_overlaps(&read, min_fraction);
+            }
+            if read.chains.is_empty() {
+                return None;
+            }
+            highlighted |= report_highlighted_anchor(&read, args);
+            Some(read)
+        });
+    let plotted = plot_stream(reads, output, options);
+    if !highlighted {
+        report_missing_highlight(args);
+    }
+
+    report_parse_summary(parsed, skipped);
+    if dropped > 0 {
+        log::info!("filtered out {dropped} chains");
+    }
+    if let Some(e) = io_error {
+        return Err(e);
+    }
+    write_manifest_file(output, &plotted)
 }
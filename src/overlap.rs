@@ -0,0 +1,58 @@
+use std::fmt;
+
+use crate::Read;
+
+/// Two chains of a read covering largely the same part of the query, which
+/// usually means one of them is redundant.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Overlap {
+    pub read: String,
+    pub first: usize,
+    pub second: usize,
+    /// Shared query bases as a fraction of the shorter of the two spans.
+    pub fraction: f64,
+}
+
+impl fmt::Display for Overlap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "read {} chains {} and {}: query spans overlap by {:.1}%",
+            self.read,
+            self.first,
+            self.second,
+            self.fraction * 100.0
+        )
+    }
+}
+
+/// Fraction of the shorter query span that the other span also covers; 0 when
+/// either span is empty.
+pub fn overlap_fraction(a: [u32; 2], b: [u32; 2]) -> f64 {
+    let shorter = a[1].saturating_sub(a[0]).min(b[1].saturating_sub(b[0]));
+    if shorter == 0 {
+        return 0.0;
+    }
+    let shared = a[1].min(b[1]).saturating_sub(a[0].max(b[0]));
+    f64::from(shared) / f64::from(shorter)
+}
+
+/// Finds every pair of chains whose query spans overlap by more than
+/// `min_fraction`, in chain order.
+pub fn find_overlaps(read: &Read, min_fraction: f64) -> Vec<Overlap> {
+    let mut overlaps = Vec::new();
+    for (first, a) in read.chains.iter().enumerate() {
+        for (second, b) in read.chains.iter().enumerate().skip(first + 1) {
+            let fraction = overlap_fraction(a.qspan, b.qspan);
+            if fraction > min_fraction {
+                overlaps.push(Overlap {
+                    read: read.name.clone(),
+                    first,
+                    second,
+                    fraction,
+                });
+            }
+        }
+    }
+    overlaps
+}
@@ -0,0 +1,247 @@
+use std::{collections::HashMap, str::FromStr};
+
+use crate::{
+    Anchor, Chain, Read,
+    cigar::cigar_ops,
+    filter::ReadFilter,
+    parse::{ParseError, ParsedReads, parse_value},
+    refs::RefNames,
+};
+
+/// Seed length used when tiling CIGAR match blocks into anchors.
+pub const PAF_ANCHOR_K: u32 = 15;
+
+const PAF_COLUMNS: usize = 12;
+
+/// Parses PAF records into reads, grouping records by query name and keeping
+/// the first `n` reads accepted by `filter`. Target names already in
+/// `ref_names` keep their `ref_id`; the others are numbered after the largest
+/// known id in order of first appearance and added to `ref_names`. Malformed
+/// records are skipped and reported alongside their line index.
+pub fn parse_paf(
+    bytes: &[u8],
+    n: Option<usize>,
+    filter: &ReadFilter,
+    ref_names: &mut RefNames,
+) -> ParsedReads {
+    let mut reads: Vec<Read> = Vec::new();
+    let mut read_index: HashMap<String, usize> = HashMap::new();
+    let mut ref_ids: HashMap<String, u32> = ref_names
+        .names()
+        .map(|(id, name)| (name.to_owned(), id))
+        .collect();
+    let mut next_id = ref_ids.values().max().map_or(0, |id| id + 1);
+    let mut errors = Vec::new();
+
+    let mut offset = 0;
+    for (line_idx, line) in bytes.split(|b| *b == b'\n').enumerate() {
+        let line_offset = offset;
+        offset += line.len() + 1;
+        if line.iter().all(u8::is_ascii_whitespace) || line.starts_with(b"#") {
+            continue;
+        }
+
+        let (query_name, query_len, target_name, chain) =
+            match parse_record(bytes, line_offset, line) {
+                Ok(record) => record,
+                Err(e) => {
+                    errors.push((line_idx, e));
+                    continue;
+                }
+            };
+
+        let ref_id = match ref_ids.get(&target_name) {
+            Some(&id) => id,
+            None => {
+                let id = next_id;
+                next_id += 1;
+                ref_names.insert(id, target_name.clone());
+                ref_ids.insert(target_name, id);
+                id
+            }
+        };
+
+        let idx = match read_index.get(&query_name) {
+            Some(&idx) => idx,
+            None => {
+                read_index.insert(query_name.clone(), reads.len());
+                reads.push(Read {
+                    name: query_name,
+                    read_len: query_len,
+                    k: PAF_ANCHOR_K,
+                    fwd_anchors: Vec::new(),
+                    rev_anchors: Vec::new(),
+                    chains: Vec::new(),
+                    cigar_count: None,
+                });
+                reads.len() - 1
+            }
+        };
+
+        let read = &mut reads[idx];
+        let background = if chain.is_revcomp {
+            &mut read.rev_anchors
+        } else {
+            &mut read.fwd_anchors
+        };
+        background.extend(chain.anchors.iter().cloned());
+        read.chains.push(Chain { ref_id, ..chain });
+    }
+
+    for read in &mut reads {
+        if !read.chains.iter().any(|chain| chain.considered) {
+            read.chains[0].considered = true;
+        }
+    }
+    let mut accepted = 0;
+    reads.retain(|read| {
+        if !filter.keeps(read) {
+            return false;
+        }
+        accepted += 1;
+        filter.samples(accepted - 1)
+    });
+    if let Some(max) = n {
+        reads.truncate(max);
+    }
+
+    (reads, errors)
+}
+
+/// Parses one PAF line into its query name, query length, target name and chain.
+fn parse_record(
+    bytes: &[u8],
+    line_offset: usize,
+    line: &[u8],
+) -> Result<(String, u32, String, Chain), ParseError> {
+    let mut columns = Vec::new();
+    let mut start = 0;
+    for (i, b) in line.iter().enumerate() {
+        if *b == b'\t' {
+            columns.push((line_offset + start, line_offset + i));
+            start = i + 1;
+        }
+    }
+    columns.push((line_offset + start, line_offset + line.len()));
+    if columns.len() < PAF_COLUMNS {
+        return Err(ParseError::new(bytes, line_offset, "paf columns"));
+    }
+
+    fn column<T: FromStr>(
+        bytes: &[u8],
+        columns: &[(usize, usize)],
+        idx: usize,
+        field: &'static str,
+    ) -> Result<T, ParseError> {
+        let (start, end) = columns[idx];
+        parse_value(bytes, start, end, field)
+    }
+    let query_name: String = column(bytes, &columns, 0, "query name")?;
+    let query_len: u32 = column(bytes, &columns, 1, "query length")?;
+    let query_start: u32 = column(bytes, &columns, 2, "query start")?;
+    let query_end: u32 = column(bytes, &columns, 3, "query end")?;
+    let is_revcomp = match column::<char>(bytes, &columns, 4, "strand")? {
+        '+' => false,
+        '-' => true,
+        _ => return Err(ParseError::new(bytes, columns[4].0, "strand")),
+    };
+    let target_name: String = column(bytes, &columns, 5, "target name")?;
+    let target_start: u32 = column(bytes, &columns, 7, "target start")?;
+    let target_end: u32 = column(bytes, &columns, 8, "target end")?;
+    let matches: u32 = column(bytes, &columns, 9, "residue matches")?;
+    let mapq: u8 = column(bytes, &columns, 11, "mapping quality")?;
+    if query_start > query_end || query_end > query_len {
+        return Err(ParseError::new(bytes, columns[3].0, "query end").because(format!(
+            "query span {query_start}-{query_end} is inverted or runs past the {query_len} bases of the query"
+        )));
+    }
+    if target_start > target_end {
+        return Err(
+            ParseError::new(bytes, columns[8].0, "target end").because(format!(
+                "target span {target_start}-{target_end} is inverted"
+            )),
+        );
+    }
+
+    let mut score = f64::from(matches);
+    let mut primary = None;
+    let mut cigar = String::new();
+    for &(start, end) in &columns[PAF_COLUMNS..] {
+        let tag = String::from_utf8_lossy(&bytes[start..end]);
+        let tag = tag.trim();
+        if let Some(value) = tag.strip_prefix("AS:i:") {
+            score = parse_value(value.as_bytes(), 0, value.len(), "AS tag")
+                .map_err(|_| ParseError::new(bytes, start, "AS tag"))?;
+        } else if let Some(value) = tag.strip_prefix("tp:A:") {
+            primary = Some(value == "P");
+        } else if let Some(value) = tag.strip_prefix("cg:Z:") {
+            cigar = value.to_owned();
+        }
+    }
+
+    // Reverse-strand chains are kept in reverse-complement query coordinates,
+    // like the dump format, so the alignment starts after the trailing clip.
+    let clip_start = if is_revcomp {
+        query_len.saturating_sub(query_end)
+    } else {
+        query_start
+    };
+    let clip_end = query_len.saturating_sub(clip_start + (query_end - query_start));
+    let anchors = match_anchors(&cigar, target_start, clip_start);
+    if !cigar.is_empty() {
+        if clip_start > 0 {
+            cigar.insert_str(0, &format!("{clip_start}S"));
+        }
+        if clip_end > 0 {
+            cigar.push_str(&format!("{clip_end}S"));
+        }
+    }
+
+    let chain = Chain {
+        ref_id: 0,
+        score,
+        // 255 marks a missing mapping quality in PAF.
+        mapq: (mapq != 255).then_some(mapq),
+        qspan: [query_start, query_end],
+        rspan: [target_start, target_end],
+        is_revcomp,
+        anchors,
+        cigar,
+        ref_start: target_start,
+        considered: primary.unwrap_or(false),
+        ssw_cigar: String::new(),
+        ssw_ref_start: target_start,
+    };
+    Ok((query_name, query_len, target_name, chain))
+}
+
+/// Tiles the match blocks of a CIGAR with non-overlapping `PAF_ANCHOR_K`-long anchors.
+fn match_anchors(cigar: &str, ref_start: u32, query_start: u32) -> Vec<Anchor> {
+    let mut anchors = Vec::new();
+    let mut ref_pos = ref_start;
+    let mut query_pos = query_start;
+    for (count, operation) in cigar_ops(cigar) {
+        match operation {
+            'M' | '=' => {
+                let mut offset = 0;
+                while offset + PAF_ANCHOR_K <= count {
+                    anchors.push(Anchor {
+                        ref_start: ref_pos + offset,
+                        query_start: query_pos + offset,
+                    });
+                    offset += PAF_ANCHOR_K;
+                }
+                ref_pos += count;
+                query_pos += count;
+            }
+            'X' => {
+                ref_pos += count;
+                query_pos += count;
+            }
+            'I' | 'S' => query_pos += count,
+            'D' | 'N' => ref_pos += count,
+            _ => {}
+        }
+    }
+    anchors
+}
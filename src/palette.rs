@@ -0,0 +1,172 @@
+use clap::ValueEnum;
+use plotters::style::{
+    BLACK, BLUE, GREEN, Palette, Palette99, RED, RGBColor, WHITE,
+    full_palette::{ORANGE, PURPLE},
+};
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+pub enum ColorPalette {
+    /// Blue anchors, green/red chains, purple and orange paths
+    Default,
+    /// Okabe-Ito colours, distinguishable with common colour-vision deficiencies
+    Colorblind,
+    /// Shades of grey, for black-and-white printing
+    Grayscale,
+}
+
+/// A colour together with the name used for it in plot legends.
+#[derive(Debug, Clone, Copy)]
+pub struct NamedColor {
+    pub color: RGBColor,
+    pub name: &'static str,
+}
+
+const fn named(color: RGBColor, name: &'static str) -> NamedColor {
+    NamedColor { color, name }
+}
+
+/// Colours of the series drawn in a chain plot.
+#[derive(Debug, Clone, Copy)]
+pub struct Colors {
+    pub background_anchors: NamedColor,
+    pub considered: NamedColor,
+    pub unconsidered: NamedColor,
+    pub piecewise: NamedColor,
+    pub ssw: NamedColor,
+    /// Colours told apart by index, for plots with one series per chain or
+    /// per input.
+    pub series: &'static [(u8, u8, u8)],
+}
+
+impl Colors {
+    /// Colour of the `idx`th series, cycling when there are more series than
+    /// colours. Unlike the named colours it is not adjusted by
+    /// [`Theme::colors`], so pass it through [`Theme::series`].
+    pub fn series(&self, idx: usize) -> RGBColor {
+        let (r, g, b) = self.series[idx % self.series.len()];
+        RGBColor(r, g, b)
+    }
+}
+
+/// Okabe-Ito colours without black, which disappears on dark backgrounds.
+const OKABE_ITO: [(u8, u8, u8); 7] = [
+    (0x00, 0x72, 0xB2),
+    (0xE6, 0x9F, 0x00),
+    (0x00, 0x9E, 0x73),
+    (0xD5, 0x5E, 0x00),
+    (0x56, 0xB4, 0xE9),
+    (0xCC, 0x79, 0xA7),
+    (0xF0, 0xE4, 0x42),
+];
+
+/// Greys far enough apart to be told apart in print.
+const GREYS: [(u8, u8, u8); 4] = [
+    (0x00, 0x00, 0x00),
+    (0x60, 0x60, 0x60),
+    (0x90, 0x90, 0x90),
+    (0xB8, 0xB8, 0xB8),
+];
+
+impl ColorPalette {
+    pub fn colors(self) -> Colors {
+        match self {
+            ColorPalette::Default => Colors {
+                background_anchors: named(BLUE, "Blue"),
+                considered: named(GREEN, "Green"),
+                unconsidered: named(RED, "Red"),
+                piecewise: named(PURPLE, "Purple"),
+                ssw: named(ORANGE, "Orange"),
+                series: Palette99::COLORS,
+            },
+            ColorPalette::Colorblind => Colors {
+                background_anchors: named(RGBColor(0x56, 0xB4, 0xE9), "Sky blue"),
+                considered: named(RGBColor(0x00, 0x9E, 0x73), "Bluish green"),
+                unconsidered: named(RGBColor(0xD5, 0x5E, 0x00), "Vermillion"),
+                piecewise: named(RGBColor(0xCC, 0x79, 0xA7), "Reddish purple"),
+                ssw: named(RGBColor(0xE6, 0x9F, 0x00), "Orange"),
+                series: &OKABE_ITO,
+            },
+            ColorPalette::Grayscale => Colors {
+                background_anchors: named(RGBColor(0xA0, 0xA0, 0xA0), "Light grey"),
+                considered: named(RGBColor(0x00, 0x00, 0x00), "Black"),
+                unconsidered: named(RGBColor(0x70, 0x70, 0x70), "Grey"),
+                piecewise: named(RGBColor(0x40, 0x40, 0x40), "Dark grey"),
+                ssw: named(RGBColor(0xC8, 0xC8, 0xC8), "Silver"),
+                series: &GREYS,
+            },
+        }
+    }
+}
+
+/// Control points of a viridis-like gradient, from low to high.
+const GRADIENT: [RGBColor; 5] = [
+    RGBColor(0x44, 0x01, 0x54),
+    RGBColor(0x3B, 0x52, 0x8B),
+    RGBColor(0x21, 0x91, 0x8C),
+    RGBColor(0x5E, 0xC9, 0x62),
+    RGBColor(0xFD, 0xE7, 0x25),
+];
+
+/// Colour at `t` along a dark purple to yellow gradient; `t` is clamped to
+/// `0..=1`.
+pub fn gradient(t: f64) -> RGBColor {
+    let t = t.clamp(0.0, 1.0) * (GRADIENT.len() - 1) as f64;
+    let idx = (t.floor() as usize).min(GRADIENT.len() - 2);
+    let frac = t - idx as f64;
+    let (from, to) = (GRADIENT[idx], GRADIENT[idx + 1]);
+    let mix = |a: u8, b: u8| (f64::from(a) + (f64::from(b) - f64::from(a)) * frac).round() as u8;
+    RGBColor(mix(from.0, to.0), mix(from.1, to.1), mix(from.2, to.2))
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+pub enum Theme {
+    Light,
+    /// Near-black background with white text and brightened series
+    Dark,
+}
+
+impl Theme {
+    pub fn background(self) -> RGBColor {
+        match self {
+            Theme::Light => WHITE,
+            Theme::Dark => RGBColor(0x12, 0x12, 0x12),
+        }
+    }
+
+    /// Colour of text, axes and borders.
+    pub fn foreground(self) -> RGBColor {
+        match self {
+            Theme::Light => BLACK,
+            Theme::Dark => WHITE,
+        }
+    }
+
+    pub fn is_dark(self) -> bool {
+        matches!(self, Theme::Dark)
+    }
+
+    /// Lightens a series colour so it stands out against a dark background.
+    pub fn series(self, color: RGBColor) -> RGBColor {
+        const LIFT: f64 = 0.4;
+        let lift = |c: u8| c + ((255 - c) as f64 * LIFT) as u8;
+        match self {
+            Theme::Light => color,
+            Theme::Dark => RGBColor(lift(color.0), lift(color.1), lift(color.2)),
+        }
+    }
+
+    pub fn colors(self, colors: Colors) -> Colors {
+        let series = |named: NamedColor| NamedColor {
+            color: self.series(named.color),
+            ..named
+        };
+        Colors {
+            background_anchors: series(colors.background_anchors),
+            considered: series(colors.considered),
+            unconsidered: series(colors.unconsidered),
+            piecewise: series(colors.piecewise),
+            ssw: series(colors.ssw),
+            series: colors.series,
+        }
+    }
+}
@@ -0,0 +1,532 @@
+use rayon::prelude::*;
+use std::{fmt, str::FromStr};
+
+use crate::{Anchor, Chain, Read, filter::ReadFilter};
+
+#[derive(Debug)]
+pub struct ParseError {
+    pub offset: usize,
+    pub field: &'static str,
+    pub snippet: String,
+    /// Why a value that was read is unusable, when it is well-formed.
+    pub reason: Option<String>,
+}
+
+impl ParseError {
+    pub(crate) fn new(bytes: &[u8], offset: usize, field: &'static str) -> Self {
+        let start = offset.saturating_sub(SNIPPET_RADIUS).min(bytes.len());
+        let end = (offset + SNIPPET_RADIUS).min(bytes.len());
+        ParseError {
+            offset,
+            field,
+            snippet: String::from_utf8_lossy(&bytes[start..end]).into_owned(),
+            reason: None,
+        }
+    }
+
+    pub(crate) fn because(mut self, reason: String) -> Self {
+        self.reason = Some(reason);
+        self
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "could not read {} at byte {} near {:?}",
+            self.field, self.offset, self.snippet
+        )?;
+        match &self.reason {
+            Some(reason) => write!(f, ": {reason}"),
+            None => Ok(()),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+const SNIPPET_RADIUS: usize = 24;
+
+pub(crate) fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+fn is_key_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'_'
+}
+
+fn is_separator(b: u8) -> bool {
+    b.is_ascii_whitespace() || b == b','
+}
+
+pub(crate) fn parse_value<T: FromStr>(
+    block: &[u8],
+    start: usize,
+    end: usize,
+    field: &'static str,
+) -> Result<T, ParseError> {
+    std::str::from_utf8(&block[start..end])
+        .ok()
+        .and_then(|s| s.trim().parse().ok())
+        .ok_or_else(|| ParseError::new(block, start, field))
+}
+
+/// A delimited region of a block, such as `{ref_id=0,score=1.5,...}`, whose
+/// `key=value` fields can be looked up by name in any order.
+struct Record<'a> {
+    block: &'a [u8],
+    start: usize,
+    end: usize,
+}
+
+impl<'a> Record<'a> {
+    /// Returns the offset of the value following `key =`, if the key is present.
+    fn value_offset(&self, key: &str) -> Option<usize> {
+        let haystack = &self.block[self.start..self.end];
+        let key = key.as_bytes();
+        let mut from = 0;
+        while let Some(at) = find(&haystack[from..], key) {
+            let at = from + at;
+            let mut j = at + key.len();
+            while haystack.get(j).is_some_and(|b| *b == b' ') {
+                j += 1;
+            }
+            if (at == 0 || !is_key_byte(haystack[at - 1])) && haystack.get(j) == Some(&b'=') {
+                return Some(self.start + j + 1);
+            }
+            from = at + 1;
+        }
+        None
+    }
+
+    /// Returns the end of the value starting at `start`: the next comma or the
+    /// end of the record.
+    fn value_end(&self, start: usize) -> usize {
+        self.block[start..self.end]
+            .iter()
+            .position(|b| *b == b',')
+            .map_or(self.end, |len| start + len)
+    }
+
+    fn field<T: FromStr>(&self, key: &'static str) -> Result<T, ParseError> {
+        let start = self
+            .value_offset(key)
+            .ok_or_else(|| ParseError::new(self.block, self.start, key))?;
+        parse_value(self.block, start, self.value_end(start), key)
+    }
+
+    /// Reads a floating-point field, with optional sign and exponent, rejecting
+    /// `NaN` and infinities so they cannot poison score comparisons.
+    fn finite_field(&self, key: &'static str) -> Result<f64, ParseError> {
+        let value: f64 = self.field(key)?;
+        if value.is_finite() {
+            Ok(value)
+        } else {
+            Err(ParseError::new(
+                self.block,
+                self.value_offset(key).unwrap_or(self.start),
+                key,
+            )
+            .because(format!("{key} must be finite")))
+        }
+    }
+
+    /// Like `field`, but `None` when the key is absent.
+    fn optional_field<T: FromStr>(&self, key: &'static str) -> Result<Option<T>, ParseError> {
+        self.value_offset(key)
+            .map(|start| parse_value(self.block, start, self.value_end(start), key))
+            .transpose()
+    }
+
+    /// Reads the unnamed value at the start of the record.
+    fn leading<T: FromStr>(&self, field: &'static str) -> Result<T, ParseError> {
+        parse_value(self.block, self.start, self.value_end(self.start), field)
+    }
+}
+
+/// Cursor over one `Query:` block.
+struct Scanner<'a> {
+    block: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Scanner<'a> {
+    fn new(block: &'a [u8]) -> Self {
+        Scanner { block, pos: 0 }
+    }
+
+    fn error(&self, field: &'static str) -> ParseError {
+        ParseError::new(self.block, self.pos, field)
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.block.get(self.pos).copied()
+    }
+
+    fn skip_separators(&mut self) {
+        while self.peek().is_some_and(is_separator) {
+            self.pos += 1;
+        }
+    }
+
+    /// Moves the cursor past the next occurrence of `marker`.
+    fn seek(&mut self, marker: &[u8]) -> bool {
+        match find(&self.block[self.pos..], marker) {
+            Some(at) => {
+                self.pos += at + marker.len();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Moves the cursor to the next occurrence of `byte` after the cursor.
+    fn seek_byte(&mut self, byte: u8) -> bool {
+        match self.block[self.pos..].iter().position(|b| *b == byte) {
+            Some(at) => {
+                self.pos += at;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Consumes the rest of the current line. The record excludes the line
+    /// ending, `\n` or `\r\n`.
+    fn line(&mut self) -> Record<'a> {
+        let start = self.pos;
+        let end = self.block[start..]
+            .iter()
+            .position(|b| *b == b'\n')
+            .map_or(self.block.len(), |len| start + len);
+        self.pos = (end + 1).min(self.block.len());
+        let end = if end > start && self.block[end - 1] == b'\r' {
+            end - 1
+        } else {
+            end
+        };
+        Record {
+            block: self.block,
+            start,
+            end,
+        }
+    }
+
+    /// Consumes a bracketed group opening at the cursor and returns its contents.
+    fn group(&mut self, open: u8, field: &'static str) -> Result<Record<'a>, ParseError> {
+        self.skip_separators();
+        if self.peek() != Some(open) {
+            return Err(self.error(field));
+        }
+        let start = self.pos + 1;
+        let mut depth = 0usize;
+        for (offset, b) in self.block[self.pos..].iter().enumerate() {
+            match b {
+                b'{' | b'[' | b'(' => depth += 1,
+                b'}' | b']' | b')' => depth -= 1,
+                _ => {}
+            }
+            if depth == 0 {
+                let end = self.pos + offset;
+                self.pos = end + 1;
+                return Ok(Record {
+                    block: self.block,
+                    start,
+                    end,
+                });
+            }
+        }
+        Err(self.error(field))
+    }
+
+    /// Iterates over the `open`-delimited items of a list whose `[` is at the cursor.
+    fn list(&mut self, open: u8, field: &'static str) -> Result<Vec<Record<'a>>, ParseError> {
+        let list = self.group(b'[', field)?;
+        let mut items = Scanner {
+            block: &self.block[..list.end],
+            pos: list.start,
+        };
+        let mut records = Vec::new();
+        loop {
+            items.skip_separators();
+            if items.peek().is_none() {
+                return Ok(records);
+            }
+            records.push(items.group(open, field)?);
+        }
+    }
+}
+
+fn parse_anchors(scanner: &mut Scanner) -> Result<Vec<Anchor>, ParseError> {
+    scanner
+        .list(b'{', "anchors")?
+        .iter()
+        .map(|item| {
+            let ref_end = item.value_end(item.start);
+            Ok(Anchor {
+                ref_start: parse_value(item.block, item.start, ref_end, "anchor ref_start")?,
+                query_start: parse_value(
+                    item.block,
+                    (ref_end + 1).min(item.end),
+                    item.end,
+                    "anchor query_start",
+                )?,
+            })
+        })
+        .collect()
+}
+
+fn parse_chains(scanner: &mut Scanner) -> Result<Vec<Chain>, ParseError> {
+    scanner
+        .list(b'{', "chains")?
+        .iter()
+        .map(|record| {
+            let mut anchors = Scanner {
+                block: &record.block[..record.end],
+                pos: record
+                    .value_offset("anchors")
+                    .ok_or_else(|| ParseError::new(record.block, record.start, "anchors"))?,
+            };
+            Ok(Chain {
+                ref_id: record.field("ref_id")?,
+                score: record.finite_field("score")?,
+                mapq: record.optional_field("mapq")?,
+                qspan: [record.field("query_start")?, record.field("query_end")?],
+                rspan: [record.field("ref_start")?, record.field("ref_end")?],
+                is_revcomp: record.field("is_revcomp")?,
+                anchors: parse_anchors(&mut anchors)?,
+                cigar: "".to_owned(),
+                ref_start: 0,
+                considered: false,
+                ssw_cigar: "".to_owned(),
+                ssw_ref_start: 0,
+            })
+        })
+        .collect()
+}
+
+/// Fills in the chains' CIGARs by position and returns how many entries the
+/// `Cigars` block has; entries beyond the last chain are ignored.
+fn parse_cigars(scanner: &mut Scanner, chains: &mut [Chain]) -> Result<usize, ParseError> {
+    if !scanner.seek_byte(b'[') {
+        return Err(scanner.error("cigars"));
+    }
+    let records = scanner.list(b'(', "cigars")?;
+    for (chain, record) in chains.iter_mut().zip(&records) {
+        chain.cigar = record.leading("cigar")?;
+        chain.considered = record.field::<u8>("was_considered")? == 1;
+        chain.ref_start = record.field("rstart")?;
+        chain.ssw_cigar = record.field("ssw")?;
+        chain.ssw_ref_start = record.field("ssw_rstart")?;
+    }
+    Ok(records.len())
+}
+
+/// Parses one `Query:` block. Sections and `key=value` fields are located by
+/// name, so extra whitespace and reordered fields are tolerated. The read
+/// length is the first value on the line after the name:
+///
+/// ```text
+/// Query: <name>
+/// L=<read_len>,k=<k>
+/// Anchors for forward strand [{<ref_start>,<query_start>}...]
+/// Anchors for reverse strand [{<ref_start>,<query_start>}...]
+/// Chains[{ref_id=..,score=..,[mapq=..,]query_start=..,query_end=..,ref_start=..,ref_end=..,is_revcomp=..,anchors=[...]}...]
+/// Cigars:[(<cigar>,was_considered=..,rstart=..,ssw=..,ssw_rstart=..)...]
+/// ```
+pub(crate) fn parse_reads(block: &[u8], mapping_only: bool) -> Result<Option<Read>, ParseError> {
+    let mut scanner = Scanner::new(block);
+    if !scanner.seek(QUERY_MARKER) {
+        return Err(scanner.error("name"));
+    }
+    let name = scanner.line().leading("name")?;
+
+    let header = scanner.line();
+    let read_len_offset = header
+        .value_offset("L")
+        .ok_or_else(|| ParseError::new(block, header.start, "read_len"))?;
+    let read_len = parse_value(
+        block,
+        read_len_offset,
+        header.value_end(read_len_offset),
+        "read_len",
+    )?;
+    let k = header.field("k")?;
+    if !(1..=MAX_K).contains(&k) {
+        let offset = header.value_offset("k").unwrap_or(header.start);
+        return Err(
+            ParseError::new(block, offset, "k").because(format!("k must be between 1 and {MAX_K}"))
+        );
+    }
+
+    let mut fwd_anchors = Vec::new();
+    if scanner.seek(b"Anchors for forward strand") {
+        fwd_anchors = parse_anchors(&mut scanner)?;
+    }
+
+    let mut rev_anchors = Vec::new();
+    if scanner.seek(b"Anchors for reverse strand") {
+        rev_anchors = parse_anchors(&mut scanner)?;
+    }
+
+    let mut chains = Vec::new();
+    if scanner.seek(b"Chains") {
+        chains = parse_chains(&mut scanner)?;
+    }
+    if chains.is_empty() {
+        return Ok(None);
+    }
+
+    let cigar_count = if mapping_only {
+        for (idx, chain) in chains.iter_mut().enumerate() {
+            chain.considered = idx == 0;
+        }
+        None
+    } else {
+        Some(parse_cigars(&mut scanner, &mut chains)?)
+    };
+
+    Ok(Some(Read {
+        name,
+        read_len,
+        k,
+        fwd_anchors,
+        rev_anchors,
+        chains,
+        cigar_count,
+    }))
+}
+
+/// Longest seed length accepted; anything longer is taken for a corrupt
+/// header rather than a real k-mer size.
+pub const MAX_K: u32 = 1024;
+
+pub(crate) const QUERY_MARKER: &[u8] = b"Query: ";
+
+/// Splits the dump into `Query:`-delimited blocks, each paired with its byte
+/// offset. Blocks are found one at a time, as they are consumed.
+fn query_blocks(bytes: &[u8]) -> impl Iterator<Item = (usize, &[u8])> {
+    let mut cursor = find(bytes, QUERY_MARKER).unwrap_or(bytes.len());
+    std::iter::from_fn(move || {
+        if cursor >= bytes.len() {
+            return None;
+        }
+        let start = cursor;
+        cursor = find(&bytes[start + 1..], QUERY_MARKER).map_or(bytes.len(), |at| start + 1 + at);
+        Some((start, &bytes[start..cursor]))
+    })
+}
+
+/// Parses the reads of a dump lazily, one `Query:` block per call to `next`,
+/// so callers can filter or stop early without building the whole `Vec`.
+///
+/// Blocks without chains are skipped. A malformed block yields its error, with
+/// the offset into `bytes`, and the iterator continues with the next block.
+pub fn parse_reads_iter(bytes: &[u8]) -> impl Iterator<Item = Result<Read, ParseError>> + '_ {
+    query_blocks(bytes).filter_map(|(start, block)| match parse_reads(block, false) {
+        Ok(read) => read.map(Ok),
+        Err(mut e) => {
+            e.offset += start;
+            Some(Err(e))
+        }
+    })
+}
+
+/// Number of blocks parsed in parallel before checking the `-n` limit, so a
+/// small limit does not pay for parsing the whole file.
+pub(crate) const PARSE_BATCH: usize = 4096;
+
+/// Parsed reads, with every malformed block or record that was skipped and
+/// its index.
+pub type ParsedReads = (Vec<Read>, Vec<(usize, ParseError)>);
+
+/// Gathers batches of parsed blocks in file order, keeping the reads accepted
+/// by the filter and stopping after `n` of them when given.
+pub(crate) struct ReadCollector<'a> {
+    n: Option<usize>,
+    mapping_only: bool,
+    filter: &'a ReadFilter,
+    reads: Vec<Read>,
+    errors: Vec<(usize, ParseError)>,
+    /// Reads accepted by the filter so far, sampled or not.
+    accepted: usize,
+    /// Blocks seen so far, for the indices of malformed ones.
+    blocks: usize,
+}
+
+impl<'a> ReadCollector<'a> {
+    pub(crate) fn new(n: Option<usize>, mapping_only: bool, filter: &'a ReadFilter) -> Self {
+        ReadCollector {
+            n,
+            mapping_only,
+            filter,
+            reads: Vec::new(),
+            errors: Vec::new(),
+            accepted: 0,
+            blocks: 0,
+        }
+    }
+
+    /// Parses a batch of `(offset, block)` pairs in parallel and adds them in
+    /// order. Returns `false` once `n` reads are collected.
+    pub(crate) fn parse_batch<B: AsRef<[u8]> + Sync>(&mut self, batch: &[(usize, B)]) -> bool {
+        let parsed: Vec<_> = batch
+            .par_iter()
+            .map(|(start, block)| (*start, parse_reads(block.as_ref(), self.mapping_only)))
+            .collect();
+
+        for (start, result) in parsed {
+            let block_idx = self.blocks;
+            self.blocks += 1;
+            match result {
+                Ok(Some(read)) if self.filter.keeps(&read) => {
+                    self.accepted += 1;
+                    if !self.filter.samples(self.accepted - 1) {
+                        continue;
+                    }
+                    self.reads.push(read);
+                    if self.n.is_some_and(|max| self.reads.len() >= max) {
+                        return false;
+                    }
+                }
+                Ok(_) => {}
+                Err(mut e) => {
+                    e.offset += start;
+                    self.errors.push((block_idx, e));
+                }
+            }
+        }
+        log::debug!(
+            "parsed {} blocks so far, {} reads kept",
+            self.blocks,
+            self.reads.len()
+        );
+        true
+    }
+
+    pub(crate) fn finish(self) -> ParsedReads {
+        (self.reads, self.errors)
+    }
+}
+
+/// Parses every `Query:` block of a dump, keeping the reads accepted by
+/// `filter` and stopping after `n` of them when given. Malformed blocks are
+/// skipped and reported alongside their block index.
+///
+/// Blocks are parsed in parallel; reads and errors keep their file order.
+pub fn parse_file(
+    bytes: &[u8],
+    n: Option<usize>,
+    mapping_only: bool,
+    filter: &ReadFilter,
+) -> ParsedReads {
+    let mut collector = ReadCollector::new(n, mapping_only, filter);
+    let blocks: Vec<_> = query_blocks(bytes).collect();
+    for batch in blocks.chunks(PARSE_BATCH) {
+        if !collector.parse_batch(batch) {
+            break;
+        }
+    }
+    collector.finish()
+}
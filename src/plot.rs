@@ -0,0 +1,2198 @@
+use clap::ValueEnum;
+use indicatif::{ProgressBar, ProgressStyle};
+use plotters::{
+    chart::{ChartBuilder, ChartContext, SeriesLabelPosition},
+    coord::{Shift, cartesian::Cartesian2d, types::RangedCoordu32},
+    prelude::{
+        BitMapBackend, Circle, Cross, DrawingArea, DrawingBackend, EmptyElement, IntoDrawingArea,
+        IntoDynElement, PathElement, Rectangle, SVGBackend, Text,
+    },
+    series::{DashedLineSeries, LineSeries, PointSeries},
+    style::{
+        Color, IntoFont, RGBAColor, RGBColor, TextStyle,
+        text_anchor::{HPos, Pos, VPos},
+    },
+};
+use rayon::prelude::*;
+use serde::Serialize;
+use std::{
+    collections::{HashMap, HashSet},
+    fs::create_dir_all,
+    ops::Range,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use crate::{
+    Anchor, Chain, Read,
+    cigar::{
+        PathSegment, chain_divergence, gap_stats, identity, parse_cigar_to_path,
+        parse_cigar_to_segments, query_length_discrepancy, soft_clips,
+    },
+    log::Verbosity,
+    palette::{ColorPalette, Colors, Theme, gradient},
+    refs::RefNames,
+    template::TitleTemplate,
+};
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+pub enum ImageFormat {
+    Png,
+    Svg,
+}
+
+impl ImageFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            ImageFormat::Png => "png",
+            ImageFormat::Svg => "svg",
+        }
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+pub enum Units {
+    /// Pick bp, kb or Mb from the width of the plotted range
+    Auto,
+    Bp,
+    Kb,
+    Mb,
+}
+
+impl Units {
+    fn scale(self, span: u32) -> u32 {
+        match self {
+            Units::Auto if span >= 1_000_000 => 1_000_000,
+            Units::Auto if span >= 10_000 => 1_000,
+            Units::Auto | Units::Bp => 1,
+            Units::Kb => 1_000,
+            Units::Mb => 1_000_000,
+        }
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+pub enum AnchorStyle {
+    /// A segment per anchor with crosses at both ends
+    Lines,
+    /// A dot at the start of each anchor
+    Points,
+    /// Anchor density shaded on a grid, for reads with very many anchors
+    Heatmap,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorBy {
+    /// Considered and not-considered chains in two fixed colours
+    Considered,
+    /// A gradient from the read's lowest to highest chain score
+    Score,
+}
+
+/// Which coordinate goes on which axis, named x first.
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Axes {
+    /// Reference on x, query on y
+    #[default]
+    RefQuery,
+    /// Query on x, reference on y
+    QueryRef,
+}
+
+impl Axes {
+    /// Places a `(ref, query)` point on the chart.
+    pub fn point(self, (ref_pos, query_pos): (u32, u32)) -> (u32, u32) {
+        match self {
+            Axes::RefQuery => (ref_pos, query_pos),
+            Axes::QueryRef => (query_pos, ref_pos),
+        }
+    }
+
+    fn segment(self, [start, end]: [(u32, u32); 2]) -> [(u32, u32); 2] {
+        [self.point(start), self.point(end)]
+    }
+
+    /// Orders the reference and query ranges of a chart as its x and y ranges.
+    pub fn ranges<T>(self, reference: T, query: T) -> (T, T) {
+        match self {
+            Axes::RefQuery => (reference, query),
+            Axes::QueryRef => (query, reference),
+        }
+    }
+}
+
+/// Which plotting loops run in parallel on the rayon pool.
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Parallelism {
+    /// Reads in parallel, the chains of a read one after another
+    #[default]
+    Read,
+    /// Reads one after another, the chains of a read in parallel
+    Chain,
+    /// Both reads and chains in parallel
+    Both,
+}
+
+impl Parallelism {
+    fn across_reads(self) -> bool {
+        self != Parallelism::Chain
+    }
+
+    fn across_chains(self) -> bool {
+        self != Parallelism::Read
+    }
+}
+
+/// Number of tick labels plotters draws on each axis by default.
+const AXIS_LABELS: u32 = 10;
+
+/// Formats reference positions in `units`, with just enough decimals that
+/// neighbouring ticks over `start..end` stay distinct.
+pub fn position_formatter(units: Units, start: u32, end: u32) -> impl Fn(&u32) -> String {
+    let span = end.saturating_sub(start);
+    let scale = units.scale(span);
+    let step = (span / AXIS_LABELS).max(1);
+    let decimals = if step >= scale {
+        0
+    } else {
+        (f64::from(scale) / f64::from(step)).log10().ceil() as usize
+    };
+    let suffix = match scale {
+        1 => "",
+        1_000 => " kb",
+        _ => " Mb",
+    };
+    move |pos| {
+        if scale == 1 {
+            pos.to_string()
+        } else {
+            format!(
+                "{:.*}{suffix}",
+                decimals,
+                f64::from(*pos) / f64::from(scale)
+            )
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PlotOptions {
+    pub mapping_only: bool,
+    pub format: ImageFormat,
+    pub size: (u32, u32),
+    /// Hides the progress bar when quiet; lists every plot written, in input
+    /// order, once plotting ends when verbose.
+    pub verbosity: Verbosity,
+    /// Note in the title when the CIGAR length disagrees with the read length.
+    pub mark_cigar_mismatch: bool,
+    /// Draw all chains of a read in a single plot instead of one plot per chain.
+    pub combined: bool,
+    /// Draw the diagonal through the start of the chain as a visual reference.
+    pub diagonal: bool,
+    /// Units of the reference axis labels.
+    pub units: Units,
+    /// Colours of the plotted series.
+    pub colors: Colors,
+    /// Background and text colours; dark themes also brighten `colors`.
+    pub theme: Theme,
+    /// How background anchors are drawn.
+    pub anchor_style: AnchorStyle,
+    /// Subsample background anchors beyond this many; heatmaps always use all.
+    pub max_anchors: Option<usize>,
+    /// Names shown instead of numeric reference ids.
+    pub ref_names: RefNames,
+    /// Insertions and deletions at least this long are highlighted in CIGAR paths.
+    pub gap_threshold: u32,
+    /// Replaces the default per-chain caption.
+    pub title_template: Option<TitleTemplate>,
+    /// Write one PNG per read with a small thumbnail of every chain instead
+    /// of per-chain plots.
+    pub contact_sheet: bool,
+    /// Use a two-line per-chain caption with identity and query coverage
+    /// instead of the default one.
+    pub rich_caption: bool,
+    /// Tile all chains of a read into one image this many panels wide; each
+    /// panel is `size` pixels.
+    pub panels: Option<usize>,
+    /// Write per-chain plots into a subdirectory of the read's directory named
+    /// after the chain's reference.
+    pub group_by_ref: bool,
+    /// Draw a scale bar of a round reference length in the lower-right
+    /// corner of per-chain plots, upper-right with `Axes::QueryRef`.
+    pub scale_bar: bool,
+    /// How chains are coloured.
+    pub color_by: ColorBy,
+    /// Fixed reference range shown by every plot instead of each chain's
+    /// padded span.
+    pub ref_window: Option<(u32, u32)>,
+    /// Which coordinate goes on the x axis.
+    pub axes: Axes,
+    /// Draw a track of binned anchor counts along the reference axis.
+    pub coverage_track: bool,
+    /// Circle the anchors within `highlight_tolerance` bases of this one in
+    /// per-chain plots.
+    pub highlight_anchor: Option<Anchor>,
+    pub highlight_tolerance: u32,
+    pub parallelism: Parallelism,
+}
+
+/// The command line's defaults: one 1600x1600 PNG per chain with every
+/// optional decoration off.
+impl Default for PlotOptions {
+    fn default() -> PlotOptions {
+        PlotOptions {
+            mapping_only: false,
+            format: ImageFormat::Png,
+            size: (1600, 1600),
+            verbosity: Verbosity::Normal,
+            mark_cigar_mismatch: false,
+            combined: false,
+            diagonal: false,
+            units: Units::Auto,
+            colors: ColorPalette::Default.colors(),
+            theme: Theme::Light,
+            anchor_style: AnchorStyle::Lines,
+            max_anchors: None,
+            ref_names: RefNames::default(),
+            gap_threshold: 20,
+            title_template: None,
+            contact_sheet: false,
+            rich_caption: false,
+            panels: None,
+            group_by_ref: false,
+            scale_bar: false,
+            color_by: ColorBy::Considered,
+            ref_window: None,
+            axes: Axes::RefQuery,
+            coverage_track: false,
+            highlight_anchor: None,
+            highlight_tolerance: 5,
+            parallelism: Parallelism::Read,
+        }
+    }
+}
+
+/// Replaces characters that are unsafe in file names with `_`. Dots are kept,
+/// except a leading one, so names never become `.`, `..` or hidden files.
+pub fn sanitize_filename<S: AsRef<str>>(name: S) -> String {
+    name.as_ref()
+        .chars()
+        .enumerate()
+        .map(|(idx, c)| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' || (c == '.' && idx > 0) {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Hands out one directory name per read, suffixing names that sanitize to an
+/// already used one with `_2`, `_3`, ... so reads never share a directory.
+#[derive(Debug, Default)]
+pub struct ReadDirs {
+    seen: HashSet<String>,
+}
+
+impl ReadDirs {
+    pub fn dir_for(&mut self, read_name: &str) -> String {
+        let base = sanitize_filename(read_name);
+        let mut dir = base.clone();
+        let mut suffix = 1;
+        while !self.seen.insert(dir.clone()) {
+            suffix += 1;
+            dir = format!("{base}_{suffix}");
+        }
+        dir
+    }
+}
+
+/// Where a read's plots were written, relative to the output directory.
+#[derive(Debug, Clone, Serialize)]
+pub struct PlottedRead {
+    pub name: String,
+    /// Sanitized directory holding the read's plots.
+    pub dir: String,
+    pub chains: Vec<PlottedChain>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PlottedChain {
+    /// Path within the read's directory, including the reference directory
+    /// with `group_by_ref`; shared by every chain of a combined plot.
+    pub file: String,
+    pub score: f64,
+    pub ref_id: u32,
+    pub considered: bool,
+}
+
+/// Plots every read under `output`, returning what was written in read order.
+pub fn plot_reads(reads: Vec<Read>, output: &str, options: &PlotOptions) -> Vec<PlottedRead> {
+    create_dir_all(output).unwrap();
+
+    let total_chains: u64 = reads.iter().map(|r| r.chains.len() as u64).sum();
+
+    let pb = progress_bar(ProgressBar::new(total_chains), options);
+    pb.set_style(
+        ProgressStyle::with_template(
+            "[{elapsed_precise}] {bar:60} {pos}/{len} chains plotted ({eta})",
+        )
+        .unwrap(),
+    );
+
+    let mut read_dirs = ReadDirs::default();
+    let dirs: Vec<String> = reads
+        .iter()
+        .map(|read| read_dirs.dir_for(&read.name))
+        .collect();
+
+    let plot = |(read, dir)| plot_read(read, dir, output, options, &pb);
+    let written: Vec<PlottedRead> = if options.parallelism.across_reads() {
+        reads.par_iter().zip(dirs).map(plot).collect()
+    } else {
+        reads.iter().zip(dirs).map(plot).collect()
+    };
+
+    pb.finish();
+    print_paths(output, &written);
+    written
+}
+
+/// Plots reads as they arrive, so only the reads currently being drawn are
+/// held in memory.
+pub fn plot_stream<I>(reads: I, output: &str, options: &PlotOptions) -> Vec<PlottedRead>
+where
+    I: Iterator<Item = Read> + Send,
+{
+    create_dir_all(output).unwrap();
+
+    let pb = progress_bar(ProgressBar::new_spinner(), options);
+    pb.set_style(
+        ProgressStyle::with_template("[{elapsed_precise}] {spinner} {pos} chains plotted").unwrap(),
+    );
+
+    let mut read_dirs = ReadDirs::default();
+    let reads = reads.map(|read| {
+        let dir = read_dirs.dir_for(&read.name);
+        (read, dir)
+    });
+    let plot = |(read, dir): (Read, String)| plot_read(&read, dir, output, options, &pb);
+    let written: Vec<PlottedRead> = if options.parallelism.across_reads() {
+        let mut written: Vec<(usize, PlottedRead)> = reads
+            .enumerate()
+            .par_bridge()
+            .map(|(read_idx, read)| (read_idx, plot(read)))
+            .collect();
+        written.sort_unstable_by_key(|(read_idx, _)| *read_idx);
+        written.into_iter().map(|(_, read)| read).collect()
+    } else {
+        reads.map(plot).collect()
+    };
+
+    pb.finish();
+    print_paths(output, &written);
+    written
+}
+
+fn progress_bar(pb: ProgressBar, options: &PlotOptions) -> ProgressBar {
+    if options.verbosity == Verbosity::Quiet {
+        ProgressBar::hidden()
+    } else {
+        pb
+    }
+}
+
+/// Lists written plots once plotting is done, in read and chain order, so the
+/// log does not depend on how the work was scheduled.
+fn print_paths(output: &str, written: &[PlottedRead]) {
+    if !log::log_enabled!(log::Level::Debug) {
+        return;
+    }
+    for read in written {
+        let mut last_file = None;
+        for chain in &read.chains {
+            if last_file != Some(&chain.file) {
+                let path = Path::new(output).join(&read.dir).join(&chain.file);
+                log::debug!("{}", path.display());
+                last_file = Some(&chain.file);
+            }
+        }
+    }
+}
+
+/// Number of images `plot_read` writes for this read.
+pub fn planned_images(read: &Read, options: &PlotOptions) -> usize {
+    if options.combined || options.panels.is_some() || options.contact_sheet {
+        1
+    } else {
+        read.chains.len()
+    }
+}
+
+/// Plots every chain of a read into `dir` under `output`, returning the written
+/// files in chain order.
+pub fn plot_read(
+    read: &Read,
+    dir: String,
+    output: &str,
+    options: &PlotOptions,
+    pb: &ProgressBar,
+) -> PlottedRead {
+    let read_dir = Path::new(output).join(&dir);
+    create_dir_all(&read_dir).unwrap();
+
+    let plotted = |chain: &Chain, filepath: &Path| PlottedChain {
+        file: filepath
+            .strip_prefix(&read_dir)
+            .unwrap()
+            .to_string_lossy()
+            .into_owned(),
+        score: chain.score,
+        ref_id: chain.ref_id,
+        considered: chain.considered,
+    };
+
+    let chains = if let Some(columns) = options.panels {
+        let filepath = plot_panels(read, &read_dir, columns, options);
+        pb.inc(read.chains.len() as u64);
+        filepath.map_or_else(Vec::new, |filepath| {
+            read.chains
+                .iter()
+                .map(|chain| plotted(chain, &filepath))
+                .collect()
+        })
+    } else if options.contact_sheet {
+        let filepath = plot_contact_sheet(read, &read_dir, options);
+        pb.inc(read.chains.len() as u64);
+        filepath.map_or_else(Vec::new, |filepath| {
+            read.chains
+                .iter()
+                .map(|chain| plotted(chain, &filepath))
+                .collect()
+        })
+    } else if options.combined {
+        let filepath = plot_combined(read, &read_dir, options);
+        pb.inc(read.chains.len() as u64);
+        read.chains
+            .iter()
+            .map(|chain| plotted(chain, &filepath))
+            .collect()
+    } else {
+        let plot = |(chain_idx, chain)| {
+            let filepath = plot_chain(read, chain, chain_idx, &read_dir, options);
+            pb.inc(1);
+            Some(plotted(chain, &filepath?))
+        };
+        if options.parallelism.across_chains() {
+            read.chains
+                .par_iter()
+                .enumerate()
+                .filter_map(plot)
+                .collect()
+        } else {
+            read.chains.iter().enumerate().filter_map(plot).collect()
+        }
+    };
+
+    PlottedRead {
+        name: read.name.clone(),
+        dir,
+        chains,
+    }
+}
+
+/// Plots all chains of a read on one shared reference axis, returning the path
+/// of the written file.
+pub fn plot_combined(read: &Read, read_dir: &Path, options: &PlotOptions) -> PathBuf {
+    let filepath = read_dir.join(format!("combined.{}", options.format.extension()));
+
+    match options.format {
+        ImageFormat::Png => draw_combined(
+            BitMapBackend::new(&filepath, options.size).into_drawing_area(),
+            read,
+            options,
+        ),
+        ImageFormat::Svg => draw_combined(
+            SVGBackend::new(&filepath, options.size).into_drawing_area(),
+            read,
+            options,
+        ),
+    }
+    filepath
+}
+
+/// Pixel size of a `rows` by `columns` grid of `cell`-sized tiles, or `None`
+/// when it does not fit in `u32`.
+fn grid_size(cell: (u32, u32), rows: usize, columns: usize) -> Option<(u32, u32)> {
+    let scale = |len: u32, count: usize| len.checked_mul(u32::try_from(count).ok()?);
+    Some((scale(cell.0, columns)?, scale(cell.1, rows)?))
+}
+
+/// Tiles every chain of a read into one image, each panel keeping its own
+/// reference window, returning the path of the written file. Reads whose
+/// image would be too large to address are skipped.
+pub fn plot_panels(
+    read: &Read,
+    read_dir: &Path,
+    columns: usize,
+    options: &PlotOptions,
+) -> Option<PathBuf> {
+    let filepath = read_dir.join(format!("panels.{}", options.format.extension()));
+    let (rows, columns) = panel_grid(read, columns);
+    let Some(size) = grid_size(options.size, rows, columns) else {
+        log::error!(
+            "{}: skipping panels, {rows}x{columns} panels of {}x{} pixels are too large",
+            read.name,
+            options.size.0,
+            options.size.1
+        );
+        return None;
+    };
+
+    match options.format {
+        ImageFormat::Png => draw_panels(
+            BitMapBackend::new(&filepath, size).into_drawing_area(),
+            read,
+            columns,
+            options,
+        ),
+        ImageFormat::Svg => draw_panels(
+            SVGBackend::new(&filepath, size).into_drawing_area(),
+            read,
+            columns,
+            options,
+        ),
+    }
+    Some(filepath)
+}
+
+/// Writes `<read>_contactsheet.png`, a grid of thumbnails of every chain of a
+/// read sized from the chain count, returning the path of the written file.
+/// Reads with too many chains for the sheet to be addressed are skipped.
+pub fn plot_contact_sheet(read: &Read, read_dir: &Path, options: &PlotOptions) -> Option<PathBuf> {
+    let filepath = read_dir.join(format!(
+        "{}_contactsheet.png",
+        sanitize_filename(&read.name)
+    ));
+    let (rows, columns) = contact_sheet_grid(read.chains.len());
+    let Some(size) = grid_size(THUMBNAIL_SIZE, rows, columns) else {
+        log::error!(
+            "{}: skipping contact sheet, {rows}x{columns} thumbnails are too large",
+            read.name
+        );
+        return None;
+    };
+    draw_contact_sheet(
+        BitMapBackend::new(&filepath, size).into_drawing_area(),
+        read,
+        options,
+    );
+    Some(filepath)
+}
+
+/// The chain a diff plot shows for one input: its best-scoring considered one.
+fn diff_chain(read: &Read) -> Option<&Chain> {
+    read.chains
+        .iter()
+        .filter(|chain| chain.considered)
+        .max_by(|a, b| a.score.total_cmp(&b.score))
+}
+
+/// Plots, for every read of either input, the best considered chain of each
+/// on one shared reference window into `diff.<ext>` in the read's directory
+/// under `output`. Reads are matched by name; those of `reads[0]` come first,
+/// then those only in `reads[1]`. Returns the written files in that order.
+pub fn plot_diff(
+    reads: [&[Read]; 2],
+    labels: [&str; 2],
+    output: &str,
+    options: &PlotOptions,
+) -> Vec<PathBuf> {
+    create_dir_all(output).unwrap();
+
+    let mut by_name: [HashMap<&str, &Read>; 2] = Default::default();
+    for (side, reads) in by_name.iter_mut().zip(reads) {
+        for read in reads.iter().rev() {
+            side.insert(&read.name, read);
+        }
+    }
+    let mut seen = HashSet::new();
+    let pairs: Vec<[Option<&Read>; 2]> = reads[0]
+        .iter()
+        .chain(reads[1])
+        .filter(|read| seen.insert(read.name.as_str()))
+        .map(|read| {
+            let name = read.name.as_str();
+            [by_name[0].get(name).copied(), by_name[1].get(name).copied()]
+        })
+        .collect();
+
+    let mut read_dirs = ReadDirs::default();
+    let dirs: Vec<PathBuf> = pairs
+        .iter()
+        .map(|pair| {
+            let name = &pair.iter().flatten().next().unwrap().name;
+            Path::new(output).join(read_dirs.dir_for(name))
+        })
+        .collect();
+
+    pairs
+        .par_iter()
+        .zip(dirs)
+        .filter_map(|(pair, read_dir)| {
+            let read = pair.iter().flatten().next().unwrap();
+            if pair.iter().flatten().all(|read| diff_chain(read).is_none()) {
+                log::warn!(
+                    "{}: no considered chain in either input, skipping diff plot",
+                    read.name
+                );
+                return None;
+            }
+            create_dir_all(&read_dir).unwrap();
+            let filepath = read_dir.join(format!("diff.{}", options.format.extension()));
+            match options.format {
+                ImageFormat::Png => draw_diff(
+                    BitMapBackend::new(&filepath, options.size).into_drawing_area(),
+                    *pair,
+                    labels,
+                    options,
+                ),
+                ImageFormat::Svg => draw_diff(
+                    SVGBackend::new(&filepath, options.size).into_drawing_area(),
+                    *pair,
+                    labels,
+                    options,
+                ),
+            }
+            Some(filepath)
+        })
+        .collect()
+}
+
+/// The inputs drawn on each panel of a diff plot: one panel for both, unless
+/// their chains are on different references, whose positions are not
+/// comparable.
+pub fn diff_panels(reads: [Option<&Read>; 2]) -> Vec<Vec<usize>> {
+    match reads.map(|read| read.and_then(diff_chain)) {
+        [Some(a), Some(b)] if a.ref_id != b.ref_id => vec![vec![0], vec![1]],
+        _ => vec![vec![0, 1]],
+    }
+}
+
+/// One input's version of a read in a diff plot.
+#[derive(Clone, Copy)]
+struct DiffSide<'a> {
+    idx: usize,
+    label: &'a str,
+    read: Option<&'a Read>,
+    chain: Option<&'a Chain>,
+}
+
+/// Draws the best considered chain of each input's version of a read, one
+/// colour per input, on a reference window covering both, or on a panel each
+/// when they are on different references. Inputs without the read or without
+/// a considered chain get a note in the legend instead.
+pub fn draw_diff<DB: DrawingBackend>(
+    root: DrawingArea<DB, Shift>,
+    reads: [Option<&Read>; 2],
+    labels: [&str; 2],
+    options: &PlotOptions,
+) {
+    let read = reads.iter().flatten().next().unwrap();
+    let sides: [DiffSide; 2] = std::array::from_fn(|idx| DiffSide {
+        idx,
+        label: labels[idx],
+        read: reads[idx],
+        chain: reads[idx].and_then(diff_chain),
+    });
+    let sides_of = |panel: &[usize]| panel.iter().map(|&side| sides[side]).collect::<Vec<_>>();
+
+    root.fill(&options.theme.background()).unwrap();
+    let title = format!("Read: {}, {} vs {}", read.name, labels[0], labels[1]);
+    match diff_panels(reads).as_slice() {
+        [panel] => draw_diff_panel(&root, Some(&title), read, &sides_of(panel), options),
+        panels => {
+            let areas = titled(&root, &title, options).split_evenly((1, panels.len()));
+            for (area, panel) in areas.iter().zip(panels) {
+                draw_diff_panel(area, None, read, &sides_of(panel), options);
+            }
+        }
+    }
+    root.present().unwrap();
+}
+
+/// Draws `sides` of a diff plot on one chart, over a reference window
+/// covering their chains.
+fn draw_diff_panel<DB: DrawingBackend>(
+    area: &DrawingArea<DB, Shift>,
+    title: Option<&str>,
+    read: &Read,
+    sides: &[DiffSide],
+    options: &PlotOptions,
+) {
+    let (ref_plot_start, ref_plot_end) = sides
+        .iter()
+        .filter_map(|side| Some(chain_ref_range(side.read?, side.chain?, options)))
+        .reduce(|a, b| (a.0.min(b.0), a.1.max(b.1)))
+        .unwrap();
+    let ref_plot_end = ref_plot_end.max(ref_plot_start + 1);
+
+    let mut chart = build_chart(area, title, options, ref_plot_start..ref_plot_end, read);
+    draw_mesh(&mut chart, options, ref_plot_start, ref_plot_end);
+
+    for side in sides {
+        let color = options
+            .theme
+            .series(options.colors.series(side.idx))
+            .mix(0.7);
+        let label = side.label;
+        let label = match (side.read, side.chain) {
+            (None, _) => format!("{label}: read not in this input"),
+            (Some(_), None) => format!("{label}: no considered chain"),
+            (Some(read), Some(chain)) => {
+                draw_chain_anchors(&mut chart, read, chain, color, options.axes);
+                if !options.mapping_only {
+                    draw_path(
+                        &mut chart,
+                        &chain_segments(read, chain, &chain.cigar, chain.ref_start),
+                        color.mix(0.5),
+                        color,
+                        options,
+                    );
+                }
+                format!(
+                    "{label}: ref {} {}",
+                    options.ref_names.name(chain.ref_id),
+                    chain_legend(chain)
+                )
+            }
+        };
+        legend_entry(&mut chart, label, move |(x, y)| {
+            PathElement::new([(x, y), (x + 30, y)], color.stroke_width(4))
+        });
+    }
+
+    draw_legend(&mut chart, options.theme);
+}
+
+/// Whether a chain gets a plot, saying why when it does not. Inverted or empty
+/// spans come from buggy upstream output and would leave the chart with no
+/// reference range; chains outside `options.ref_window` have nothing to show.
+fn should_plot(read: &Read, chain: &Chain, chain_idx: usize, options: &PlotOptions) -> bool {
+    let [ref_start, ref_end] = chain.rspan;
+    if ref_end <= ref_start {
+        log::warn!(
+            "{}: chain {chain_idx} has inverted reference span {ref_start}..{ref_end}, skipping",
+            read.name
+        );
+        return false;
+    }
+    if let Some((window_start, window_end)) = options.ref_window
+        && (ref_end <= window_start || ref_start >= window_end)
+    {
+        log::info!(
+            "{}: skipping chain {chain_idx}, outside the reference window {window_start}-{window_end}",
+            read.name
+        );
+        return false;
+    }
+    true
+}
+
+/// Plots one chain into its own file, returning its path, or `None` when the
+/// chain is not plotted.
+pub fn plot_chain(
+    read: &Read,
+    chain: &Chain,
+    chain_idx: usize,
+    read_dir: &Path,
+    options: &PlotOptions,
+) -> Option<PathBuf> {
+    if !should_plot(read, chain, chain_idx, options) {
+        return None;
+    }
+
+    let filename = format!(
+        "chain_id={}_score={:.2}.{}",
+        chain_idx,
+        chain.score,
+        options.format.extension()
+    );
+    let chain_dir = if options.group_by_ref {
+        let ref_dir = read_dir.join(sanitize_filename(options.ref_names.name(chain.ref_id)));
+        create_dir_all(&ref_dir).unwrap();
+        ref_dir
+    } else {
+        read_dir.to_path_buf()
+    };
+    let filepath = chain_dir.join(filename);
+
+    match options.format {
+        ImageFormat::Png => draw_chain(
+            BitMapBackend::new(&filepath, options.size).into_drawing_area(),
+            read,
+            chain,
+            options,
+        ),
+        ImageFormat::Svg => draw_chain(
+            SVGBackend::new(&filepath, options.size).into_drawing_area(),
+            read,
+            chain,
+            options,
+        ),
+    }
+    Some(filepath)
+}
+
+/// An image rendered in memory, as rows of RGBA pixels.
+#[derive(Debug, Clone)]
+pub struct RgbaImage {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+/// Renders one chain's plot into memory instead of a file, at `options.size`.
+/// Returns `None` when the chain is not plotted.
+pub fn render_chain_rgba(
+    read: &Read,
+    chain_idx: usize,
+    options: &PlotOptions,
+) -> Option<RgbaImage> {
+    let chain = &read.chains[chain_idx];
+    if !should_plot(read, chain, chain_idx, options) {
+        return None;
+    }
+
+    let (width, height) = options.size;
+    let mut rgb = vec![0; width as usize * height as usize * 3];
+    draw_chain(
+        BitMapBackend::with_buffer(&mut rgb, options.size).into_drawing_area(),
+        read,
+        chain,
+        options,
+    );
+    let pixels = rgb
+        .chunks_exact(3)
+        .flat_map(|pixel| [pixel[0], pixel[1], pixel[2], u8::MAX])
+        .collect();
+    Some(RgbaImage {
+        width,
+        height,
+        pixels,
+    })
+}
+
+/// Maps a query position onto the plot's query axis. Reverse-complement chains
+/// are stored in reverse-strand coordinates, so they are flipped to draw with a
+/// negative slope.
+pub fn query_axis(read: &Read, is_revcomp: bool, query_pos: u32) -> u32 {
+    if is_revcomp {
+        read.read_len.saturating_sub(query_pos)
+    } else {
+        query_pos
+    }
+}
+
+/// Returns the start and end points of an anchor's `k`-long diagonal, cut
+/// short at `u32::MAX`.
+pub fn anchor_segment(read: &Read, is_revcomp: bool, anchor: &Anchor) -> [(u32, u32); 2] {
+    [
+        (
+            anchor.ref_start,
+            query_axis(read, is_revcomp, anchor.query_start),
+        ),
+        (
+            anchor.ref_start.saturating_add(read.k),
+            query_axis(read, is_revcomp, anchor.query_start.saturating_add(read.k)),
+        ),
+    ]
+}
+
+/// Returns the segments of one of a chain's CIGARs in plot coordinates.
+pub fn chain_segments(read: &Read, chain: &Chain, cigar: &str, ref_start: u32) -> Vec<PathSegment> {
+    let flip = |(ref_pos, query_pos)| (ref_pos, query_axis(read, chain.is_revcomp, query_pos));
+    parse_cigar_to_segments(cigar, ref_start)
+        .into_iter()
+        .map(|segment| PathSegment {
+            start: flip(segment.start),
+            end: flip(segment.end),
+            ..segment
+        })
+        .collect()
+}
+
+/// Returns the alignment path of one of a chain's CIGARs in plot coordinates.
+pub fn chain_path(read: &Read, chain: &Chain, cigar: &str, ref_start: u32) -> Vec<(u32, u32)> {
+    parse_cigar_to_path(cigar, ref_start)
+        .into_iter()
+        .map(|(ref_pos, query_pos)| (ref_pos, query_axis(read, chain.is_revcomp, query_pos)))
+        .collect()
+}
+
+/// Text styles of a theme, resolved once per thread and shared by every plot
+/// the thread draws instead of being rebuilt for each image. Loaded fonts
+/// cannot cross threads, so each rayon worker keeps its own.
+struct TextStyles {
+    title: TextStyle<'static>,
+    legend: TextStyle<'static>,
+    scale_bar: TextStyle<'static>,
+    colorbar: TextStyle<'static>,
+    axis_labels: TextStyle<'static>,
+}
+
+impl TextStyles {
+    fn new(theme: Theme) -> TextStyles {
+        let foreground = theme.foreground();
+        TextStyles {
+            title: ("Arial", 20).into_font().color(&foreground),
+            legend: ("Arial", 22).into_font().color(&foreground),
+            scale_bar: ("Arial", 20).into_font().color(&foreground),
+            colorbar: ("Arial", 14).into_font().color(&foreground),
+            axis_labels: ("sans-serif", 12).into_font().color(&foreground),
+        }
+    }
+
+    fn of(theme: Theme) -> Rc<TextStyles> {
+        thread_local! {
+            static LIGHT: Rc<TextStyles> = Rc::new(TextStyles::new(Theme::Light));
+            static DARK: Rc<TextStyles> = Rc::new(TextStyles::new(Theme::Dark));
+        }
+        match theme {
+            Theme::Light => LIGHT.with(Rc::clone),
+            Theme::Dark => DARK.with(Rc::clone),
+        }
+    }
+}
+
+type Chart<'a, DB> = ChartContext<'a, DB, Cartesian2d<RangedCoordu32, RangedCoordu32>>;
+
+fn anchors_in_range<'a>(
+    read: &Read,
+    anchors: &'a [Anchor],
+    ref_plot_start: u32,
+    ref_plot_end: u32,
+) -> Vec<&'a Anchor> {
+    anchors
+        .iter()
+        .filter(|anchor| {
+            anchor.ref_start >= ref_plot_start
+                && anchor.ref_start.saturating_add(read.k) <= ref_plot_end
+        })
+        .collect()
+}
+
+/// Draws the background anchors in `options.anchor_style`, keeping only every
+/// kth one when there are more than `options.max_anchors`. Returns k, which
+/// is 1 when every anchor was drawn.
+fn draw_background_anchors<DB: DrawingBackend>(
+    chart: &mut Chart<DB>,
+    segments: &[[(u32, u32); 2]],
+    color: RGBColor,
+    options: &PlotOptions,
+) -> usize {
+    let step = match (options.anchor_style, options.max_anchors) {
+        (AnchorStyle::Heatmap, _) | (_, None) => 1,
+        (_, Some(max)) => segments.len().div_ceil(max.max(1)).max(1),
+    };
+    let segments: Vec<_> = segments
+        .iter()
+        .step_by(step)
+        .map(|&segment| options.axes.segment(segment))
+        .collect();
+
+    match options.anchor_style {
+        AnchorStyle::Lines => {
+            // One series per kind of element rather than three per anchor: the
+            // anchors share a colour, so the drawing order does not show.
+            chart
+                .draw_series(
+                    segments
+                        .iter()
+                        .map(|&segment| PathElement::new(segment, color.stroke_width(2))),
+                )
+                .unwrap();
+
+            chart
+                .draw_series(PointSeries::of_element(
+                    segments.iter().flatten().copied(),
+                    10,
+                    &color,
+                    &|c, s, st| Cross::new(c, s, st.filled()),
+                ))
+                .unwrap();
+        }
+        AnchorStyle::Points => {
+            chart
+                .draw_series(PointSeries::of_element(
+                    segments.iter().map(|[start, _]| *start),
+                    3,
+                    &color,
+                    &|c, s, st| Circle::new(c, s, st.filled()),
+                ))
+                .unwrap();
+        }
+        AnchorStyle::Heatmap => draw_anchor_heatmap(chart, &segments, color),
+    }
+    step
+}
+
+/// Describes a chain for its legend entry, as `score=842.3 (-strand, considered)`.
+pub fn chain_legend(chain: &Chain) -> String {
+    format!(
+        "score={:.1} ({}strand, {})",
+        chain.score,
+        if chain.is_revcomp { '-' } else { '+' },
+        if chain.considered {
+            "considered"
+        } else {
+            "not considered"
+        }
+    )
+}
+
+fn background_label(colors: &Colors, step: usize) -> String {
+    if step > 1 {
+        format!(
+            "{}: Background anchors (1 in {step} shown)",
+            colors.background_anchors.name
+        )
+    } else {
+        format!("{}: Background anchors", colors.background_anchors.name)
+    }
+}
+
+/// Number of heatmap cells along each axis.
+const HEATMAP_BINS: u32 = 200;
+
+/// Bins anchor start points into a grid over the plot area and shades each
+/// non-empty cell by its anchor count, on a log scale so sparse cells stay
+/// visible next to repeats.
+fn draw_anchor_heatmap<DB: DrawingBackend>(
+    chart: &mut Chart<DB>,
+    segments: &[[(u32, u32); 2]],
+    color: RGBColor,
+) {
+    let x_range = chart.x_range();
+    let y_range = chart.y_range();
+    let bin_width = (x_range.end - x_range.start).div_ceil(HEATMAP_BINS).max(1);
+    let bin_height = (y_range.end - y_range.start).div_ceil(HEATMAP_BINS).max(1);
+
+    let mut counts = vec![0u32; (HEATMAP_BINS * HEATMAP_BINS) as usize];
+    for &[(x, y), _] in segments {
+        if !x_range.contains(&x) || !y_range.contains(&y) {
+            continue;
+        }
+        let col = ((x - x_range.start) / bin_width).min(HEATMAP_BINS - 1);
+        let row = ((y - y_range.start) / bin_height).min(HEATMAP_BINS - 1);
+        counts[(row * HEATMAP_BINS + col) as usize] += 1;
+    }
+
+    let max_count = counts.iter().copied().max().unwrap_or(0);
+    if max_count == 0 {
+        return;
+    }
+    let scale = f64::from(max_count).ln_1p();
+
+    let cells = counts
+        .iter()
+        .enumerate()
+        .filter(|(_, count)| **count > 0)
+        .map(|(idx, &count)| {
+            let (row, col) = (idx as u32 / HEATMAP_BINS, idx as u32 % HEATMAP_BINS);
+            let x0 = x_range.start + col * bin_width;
+            let y0 = y_range.start + row * bin_height;
+            let shade = 0.15 + 0.85 * f64::from(count).ln_1p() / scale;
+            Rectangle::new(
+                [(x0, y0), (x0 + bin_width, y0 + bin_height)],
+                color.mix(shade).filled(),
+            )
+        });
+    chart.draw_series(cells).unwrap();
+}
+
+fn background_segments(read: &Read, anchors: &[&Anchor], is_revcomp: bool) -> Vec<[(u32, u32); 2]> {
+    anchors
+        .iter()
+        .map(|anchor| anchor_segment(read, is_revcomp, anchor))
+        .collect()
+}
+
+/// Returns the connector from the end of each chain anchor to the start of
+/// the next, with whether the next anchor lies past the end of the current
+/// one on both axes rather than jumping back.
+pub fn anchor_links(read: &Read, chain: &Chain) -> Vec<([(u32, u32); 2], bool)> {
+    chain
+        .anchors
+        .windows(2)
+        .map(|pair| {
+            let from = anchor_segment(read, chain.is_revcomp, &pair[0])[1];
+            let to = anchor_segment(read, chain.is_revcomp, &pair[1])[0];
+            ([from, to], pair[0].precedes(&pair[1], read.k))
+        })
+        .collect()
+}
+
+/// Draws a chain's anchors and the connectors between consecutive anchors;
+/// connectors to an anchor that is not colinear with the previous one are
+/// dotted.
+fn draw_chain_anchors<DB: DrawingBackend>(
+    chart: &mut Chart<DB>,
+    read: &Read,
+    chain: &Chain,
+    color: RGBAColor,
+    axes: Axes,
+) {
+    let segments = chain
+        .anchors
+        .iter()
+        .map(|anchor| anchor_segment(read, chain.is_revcomp, anchor));
+    let (links, jumps): (Vec<_>, Vec<_>) = anchor_links(read, chain)
+        .into_iter()
+        .partition(|(_, colinear)| *colinear);
+
+    chart
+        .draw_series(
+            segments
+                .chain(links.into_iter().map(|(link, _)| link))
+                .map(|segment| PathElement::new(axes.segment(segment), color.stroke_width(4))),
+        )
+        .unwrap();
+    for (jump, _) in jumps {
+        chart
+            .draw_series(DashedLineSeries::new(
+                axes.segment(jump),
+                2,
+                6,
+                color.stroke_width(2),
+            ))
+            .unwrap();
+    }
+}
+
+/// Opacity of the bands over soft-clipped query.
+const CLIP_SHADE: f64 = 0.12;
+
+/// Shades the query soft-clipped at either end of the chain's CIGAR across the
+/// whole reference range. Returns the leading and trailing clip lengths.
+fn draw_clip_bands<DB: DrawingBackend>(
+    chart: &mut Chart<DB>,
+    read: &Read,
+    chain: &Chain,
+    ref_range: Range<u32>,
+    options: &PlotOptions,
+) -> (u32, u32) {
+    let (leading, trailing) = soft_clips(&chain.cigar);
+    let color = options.theme.foreground().mix(CLIP_SHADE).filled();
+    let bands = [
+        (0, leading),
+        (read.read_len.saturating_sub(trailing), read.read_len),
+    ];
+    chart
+        .draw_series(
+            bands
+                .into_iter()
+                .filter(|(start, end)| start < end)
+                .map(|(start, end)| {
+                    let start = query_axis(read, chain.is_revcomp, start);
+                    let end = query_axis(read, chain.is_revcomp, end);
+                    Rectangle::new(
+                        [
+                            options.axes.point((ref_range.start, start)),
+                            options.axes.point((ref_range.end, end)),
+                        ],
+                        color,
+                    )
+                }),
+        )
+        .unwrap();
+    (leading, trailing)
+}
+
+/// Radius in pixels of the circle around highlighted anchors.
+const HIGHLIGHT_RADIUS: u32 = 10;
+
+/// Chart points of the starts of `anchors` near `options.highlight_anchor`,
+/// empty when no anchor is highlighted.
+pub fn highlight_points<'a>(
+    read: &Read,
+    is_revcomp: bool,
+    anchors: impl Iterator<Item = &'a Anchor>,
+    options: &PlotOptions,
+) -> HashSet<(u32, u32)> {
+    let Some(target) = &options.highlight_anchor else {
+        return HashSet::new();
+    };
+    anchors
+        .filter(|anchor| anchor.is_near(target, options.highlight_tolerance))
+        .map(|anchor| {
+            options.axes.point((
+                anchor.ref_start,
+                query_axis(read, is_revcomp, anchor.query_start),
+            ))
+        })
+        .collect()
+}
+
+/// Circles the [`highlight_points`] of `anchors` in the foreground colour, so
+/// they stand out from all series. Returns whether any anchor was drawn.
+fn draw_highlighted_anchors<'a, DB: DrawingBackend>(
+    chart: &mut Chart<DB>,
+    read: &Read,
+    is_revcomp: bool,
+    anchors: impl Iterator<Item = &'a Anchor>,
+    options: &PlotOptions,
+) -> bool {
+    let points = highlight_points(read, is_revcomp, anchors, options);
+    let color = options.theme.foreground().stroke_width(3);
+    chart
+        .draw_series(
+            points
+                .iter()
+                .map(|&point| Circle::new(point, HIGHLIGHT_RADIUS, color)),
+        )
+        .unwrap();
+    !points.is_empty()
+}
+
+/// Returns the part of the diagonal through the chain's first aligned base
+/// (`rspan[0]`, `qspan[0]`) that falls within the plotted reference range.
+fn diagonal_segment(
+    read: &Read,
+    chain: &Chain,
+    ref_plot_start: u32,
+    ref_plot_end: u32,
+) -> Option<[(u32, u32); 2]> {
+    let offset = i64::from(chain.rspan[0]) - i64::from(chain.qspan[0]);
+    let start = offset.max(i64::from(ref_plot_start));
+    let end = (offset + i64::from(read.read_len)).min(i64::from(ref_plot_end));
+    if end <= start {
+        return None;
+    }
+    let point = |ref_pos: i64| {
+        let query_pos = (ref_pos - offset) as u32;
+        (
+            ref_pos as u32,
+            query_axis(read, chain.is_revcomp, query_pos),
+        )
+    };
+    Some([point(start), point(end)])
+}
+
+fn draw_mesh<DB: DrawingBackend>(
+    chart: &mut Chart<DB>,
+    options: &PlotOptions,
+    ref_plot_start: u32,
+    ref_plot_end: u32,
+) {
+    let format_position = position_formatter(options.units, ref_plot_start, ref_plot_end);
+    let mut mesh = chart.configure_mesh();
+    match options.axes {
+        Axes::RefQuery => mesh
+            .x_desc("Reference")
+            .y_desc("Query")
+            .x_label_formatter(&format_position),
+        Axes::QueryRef => mesh
+            .x_desc("Query")
+            .y_desc("Reference")
+            .y_label_formatter(&format_position),
+    };
+    if options.theme.is_dark() {
+        let foreground = options.theme.foreground();
+        mesh.axis_style(foreground)
+            .bold_line_style(foreground.mix(0.2))
+            .light_line_style(foreground.mix(0.1))
+            .label_style(TextStyles::of(options.theme).axis_labels.clone());
+    }
+    mesh.draw().unwrap();
+}
+
+/// Picks a scale bar length of 1, 2 or 5 × 10^n bases, at most a fifth of
+/// `span`.
+pub fn scale_bar_length(span: u32) -> u32 {
+    let target = (span / 5).max(1);
+    let mut magnitude = 1;
+    while magnitude <= target / 10 {
+        magnitude *= 10;
+    }
+    [5, 2, 1]
+        .into_iter()
+        .map(|factor| factor * magnitude)
+        .find(|&len| len <= target)
+        .unwrap_or(magnitude)
+}
+
+/// Labels a scale bar length such as `500 bp`, `2 kb` or `1 Mb`.
+pub fn scale_bar_label(len: u32) -> String {
+    if len >= 1_000_000 {
+        format!("{} Mb", len / 1_000_000)
+    } else if len >= 1_000 {
+        format!("{} kb", len / 1_000)
+    } else {
+        format!("{len} bp")
+    }
+}
+
+/// Draws the scale bar along the reference axis near its far end, clear of
+/// the legend in the upper-left corner.
+fn draw_scale_bar<DB: DrawingBackend>(
+    chart: &mut Chart<DB>,
+    options: &PlotOptions,
+    ref_plot_start: u32,
+    ref_plot_end: u32,
+    read_len: u32,
+) {
+    let span = ref_plot_end - ref_plot_start;
+    let len = scale_bar_length(span);
+    let end = ref_plot_end - span / 20;
+    let start = end.saturating_sub(len).max(ref_plot_start);
+    let query_pos = match options.axes {
+        Axes::RefQuery => read_len / 20,
+        Axes::QueryRef => read_len - read_len / 20,
+    };
+    let foreground = options.theme.foreground();
+
+    chart
+        .draw_series(LineSeries::new(
+            options.axes.segment([(start, query_pos), (end, query_pos)]),
+            foreground.stroke_width(3),
+        ))
+        .unwrap();
+    let (pos, offset) = match options.axes {
+        Axes::RefQuery => (Pos::new(HPos::Center, VPos::Bottom), (0, -6)),
+        Axes::QueryRef => (Pos::new(HPos::Right, VPos::Center), (-6, 0)),
+    };
+    let style = TextStyles::of(options.theme).scale_bar.pos(pos);
+    let middle = options.axes.point((start + (end - start) / 2, query_pos));
+    chart
+        .draw_series(std::iter::once(
+            EmptyElement::at(middle) + Text::new(scale_bar_label(len), offset, style),
+        ))
+        .unwrap();
+}
+
+/// Lowest and highest chain score of a read.
+fn score_range(read: &Read) -> (f64, f64) {
+    read.chains
+        .iter()
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), chain| {
+            (min.min(chain.score), max.max(chain.score))
+        })
+}
+
+/// Gradient colour of a chain's score within its read's score range.
+fn score_color(read: &Read, chain: &Chain, theme: Theme) -> RGBColor {
+    let (min, max) = score_range(read);
+    let t = if max > min {
+        (chain.score - min) / (max - min)
+    } else {
+        1.0
+    };
+    theme.series(gradient(t))
+}
+
+/// Number of bands the colour bar gradient is drawn with.
+const COLORBAR_STEPS: u32 = 50;
+
+/// Draws the score gradient as a vertical bar in the right margin of `root`,
+/// labelled with the read's highest score on top and lowest below.
+fn draw_colorbar<DB: DrawingBackend>(root: &DrawingArea<DB, Shift>, read: &Read, theme: Theme) {
+    let (min, max) = score_range(read);
+    let (width, height) = root.dim_in_pixel();
+    let (left, right) = (width as i32 - 40, width as i32 - 26);
+    let (top, bottom) = (height as i32 / 4, height as i32 * 3 / 4);
+    let band = f64::from(bottom - top) / f64::from(COLORBAR_STEPS);
+
+    for step in 0..COLORBAR_STEPS {
+        let y0 = top + (f64::from(step) * band) as i32;
+        let y1 = top + (f64::from(step + 1) * band).ceil() as i32;
+        let t = 1.0 - f64::from(step) / f64::from(COLORBAR_STEPS - 1);
+        root.draw(&Rectangle::new(
+            [(left, y0), (right, y1)],
+            theme.series(gradient(t)).filled(),
+        ))
+        .unwrap();
+    }
+    root.draw(&Rectangle::new(
+        [(left, top), (right, bottom)],
+        theme.foreground(),
+    ))
+    .unwrap();
+
+    let text = TextStyles::of(theme);
+    let font = &text.colorbar;
+    let center = (left + right) / 2;
+    root.draw(&Text::new(
+        format!("{max:.2}"),
+        (center, top - 4),
+        font.pos(Pos::new(HPos::Center, VPos::Bottom)),
+    ))
+    .unwrap();
+    root.draw(&Text::new(
+        format!("{min:.2}"),
+        (center, bottom + 4),
+        font.pos(Pos::new(HPos::Center, VPos::Top)),
+    ))
+    .unwrap();
+}
+
+/// Adds a legend entry drawn by `swatch`, through an empty series so nothing
+/// is drawn in the chart itself.
+fn legend_entry<'a, DB, E>(
+    chart: &mut Chart<'a, DB>,
+    label: impl Into<String>,
+    swatch: impl Fn((i32, i32)) -> E + 'a,
+) where
+    DB: DrawingBackend + 'a,
+    E: IntoDynElement<'a, DB, (i32, i32)>,
+{
+    chart
+        .draw_series(std::iter::empty::<EmptyElement<(u32, u32), DB>>())
+        .unwrap()
+        .label(label)
+        .legend(swatch);
+}
+
+fn draw_legend<'a, DB: DrawingBackend + 'a>(chart: &mut Chart<'a, DB>, theme: Theme) {
+    chart
+        .configure_series_labels()
+        .background_style(theme.background().mix(0.9))
+        .border_style(theme.foreground())
+        .label_font(TextStyles::of(theme).legend.clone())
+        .position(SeriesLabelPosition::UpperLeft)
+        .draw()
+        .unwrap();
+}
+
+/// Draws a CIGAR path, overlaying insertions and deletions of at least
+/// `options.gap_threshold` bases as thicker dashes in `gap_color`.
+fn draw_path<DB: DrawingBackend>(
+    chart: &mut Chart<DB>,
+    segments: &[PathSegment],
+    color: RGBAColor,
+    gap_color: RGBAColor,
+    options: &PlotOptions,
+) {
+    let Some(first) = segments.first() else {
+        return;
+    };
+    let axes = options.axes;
+    let path = std::iter::once(first.start)
+        .chain(segments.iter().map(|segment| segment.end))
+        .map(|point| axes.point(point));
+    chart
+        .draw_series(LineSeries::new(path, color.stroke_width(4)))
+        .unwrap();
+
+    for segment in segments {
+        if matches!(segment.op, 'I' | 'D' | 'N') && segment.len >= options.gap_threshold {
+            chart
+                .draw_series(DashedLineSeries::new(
+                    axes.segment([segment.start, segment.end]),
+                    12,
+                    6,
+                    gap_color.stroke_width(7),
+                ))
+                .unwrap();
+        }
+    }
+}
+
+/// Smallest margin, in bases, left around the plotted chains.
+const MIN_PADDING: u32 = 10;
+
+/// Margin around the plotted chains: half the read, but never so little that
+/// anchors of short reads sit on the plot border.
+fn plot_padding(read: &Read) -> u32 {
+    (read.read_len / 2).max(read.k).max(MIN_PADDING)
+}
+
+fn default_title(read: &Read, chain: &Chain, options: &PlotOptions) -> String {
+    let mut title = format!(
+        "Score: {:.2}, Ref ID: {}, Ref Span: {}-{}",
+        chain.score,
+        options.ref_names.name(chain.ref_id),
+        chain.rspan[0],
+        chain.rspan[1]
+    );
+    if let Some(mapq) = chain.mapq {
+        title.push_str(&format!(", MAPQ: {mapq}"));
+    }
+    if !chain.cigar.is_empty() {
+        match identity(&chain.cigar) {
+            Some(identity) => title.push_str(&format!(", Identity: {:.2}%", identity * 100.0)),
+            None => title.push_str(", Identity: n/a"),
+        }
+        let gaps = gap_stats(&chain.cigar);
+        title.push_str(&format!(
+            ", Gaps: {}I/{}bp {}D/{}bp (max {})",
+            gaps.insertions,
+            gaps.inserted_bases,
+            gaps.deletions,
+            gaps.deleted_bases,
+            gaps.largest_gap
+        ));
+    }
+    if !options.mapping_only
+        && let Some(divergence) = chain_divergence(chain)
+    {
+        title.push_str(&format!(", Divergence: {divergence:.1}"));
+    }
+    if options.mark_cigar_mismatch && !chain.cigar.is_empty() {
+        let discrepancy = query_length_discrepancy(&chain.cigar, read.read_len);
+        if discrepancy != 0 {
+            title.push_str(&format!(", CIGAR length off by {discrepancy:+}"));
+        }
+    }
+    title
+}
+
+/// Two-line caption: what the chain is on the first line, how well it aligns
+/// on the second. Undefined identity or coverage show as `n/a`.
+pub fn rich_title(chain: &Chain, read: &Read, options: &PlotOptions) -> String {
+    let percent = |fraction: Option<f64>| {
+        fraction.map_or("n/a".to_owned(), |fraction| {
+            format!("{:.2}%", fraction * 100.0)
+        })
+    };
+    format!(
+        "Score: {:.2}, Ref: {}, Span: {}-{} ({})\nIdentity: {}, Query coverage: {}",
+        chain.score,
+        options.ref_names.name(chain.ref_id),
+        chain.rspan[0],
+        chain.rspan[1],
+        if chain.is_revcomp { '-' } else { '+' },
+        percent(identity(&chain.cigar)),
+        percent(chain.query_coverage(read.read_len)),
+    )
+}
+
+/// Draws every line of `title` centred at the top of `root`, returning the
+/// area below. Plotters captions only hold one line.
+fn titled<DB: DrawingBackend>(
+    root: &DrawingArea<DB, Shift>,
+    title: &str,
+    options: &PlotOptions,
+) -> DrawingArea<DB, Shift> {
+    let style = &TextStyles::of(options.theme).title;
+    title.lines().fold(root.clone(), |area, line| {
+        area.titled(line, style.clone()).unwrap()
+    })
+}
+
+/// Sets up a chart over `ref_range` and the whole read, with the axes in
+/// `options.axes` order.
+fn build_chart<'a, DB: DrawingBackend>(
+    root: &'a DrawingArea<DB, Shift>,
+    title: Option<&str>,
+    options: &PlotOptions,
+    ref_range: Range<u32>,
+    read: &Read,
+) -> Chart<'a, DB> {
+    let query_range = 0u32..read.read_len;
+    let mut builder = ChartBuilder::on(root);
+    if let Some(title) = title {
+        builder.caption(title, TextStyles::of(options.theme).title.clone());
+    }
+    builder
+        .margin(CHART_MARGIN)
+        .x_label_area_size(X_LABEL_AREA)
+        .y_label_area_size(y_label_area(options.axes));
+    let (x_range, y_range) = options.axes.ranges(ref_range, query_range);
+    builder.build_cartesian_2d(x_range, y_range).unwrap()
+}
+
+/// Margin around charts, in pixels.
+const CHART_MARGIN: u32 = 50;
+
+/// Height of the x axis labels and description, in pixels.
+const X_LABEL_AREA: u32 = 60;
+
+/// Width of the y axis labels and description, wider when they show
+/// reference positions.
+fn y_label_area(axes: Axes) -> u32 {
+    match axes {
+        Axes::RefQuery => 40,
+        Axes::QueryRef => 60,
+    }
+}
+
+/// Number of reference bins of the coverage track.
+const COVERAGE_BINS: u32 = 100;
+
+/// Titles `root` and splits the rest into the chart area and a coverage
+/// track a sixth as deep, along the top when the reference is on x and along
+/// the right when it is on y. The track leaves the same margins as the chart
+/// along the reference axis so their bins line up.
+fn with_coverage_track<DB: DrawingBackend>(
+    root: &DrawingArea<DB, Shift>,
+    title: &str,
+    options: &PlotOptions,
+) -> (DrawingArea<DB, Shift>, DrawingArea<DB, Shift>) {
+    let area = titled(root, title, options);
+    let (width, height) = area.dim_in_pixel();
+    match options.axes {
+        Axes::RefQuery => {
+            let (track, chart) = area.split_vertically(height / 6);
+            (chart, track)
+        }
+        Axes::QueryRef => area.split_horizontally(width - width / 6),
+    }
+}
+
+/// Splits `ref_range` into at most `COVERAGE_BINS` bins of equal width and
+/// counts how many of `anchors` start in each, returning the bin width and
+/// the counts.
+pub fn coverage_bins<'a>(
+    anchors: impl IntoIterator<Item = &'a Anchor>,
+    ref_range: Range<u32>,
+) -> (u32, Vec<u32>) {
+    let span = ref_range.end - ref_range.start;
+    let bin_width = span.div_ceil(COVERAGE_BINS).max(1);
+    let mut counts = vec![0u32; span.div_ceil(bin_width) as usize];
+    for anchor in anchors {
+        if ref_range.contains(&anchor.ref_start) {
+            counts[((anchor.ref_start - ref_range.start) / bin_width) as usize] += 1;
+        }
+    }
+    (bin_width, counts)
+}
+
+/// Draws the [`coverage_bins`] of `anchors` over `ref_range` as bars.
+fn draw_coverage_track<'a, DB: DrawingBackend>(
+    area: &DrawingArea<DB, Shift>,
+    anchors: impl IntoIterator<Item = &'a Anchor>,
+    ref_range: Range<u32>,
+    color: RGBColor,
+    options: &PlotOptions,
+) {
+    let (bin_width, counts) = coverage_bins(anchors, ref_range.clone());
+    let max_count = counts.iter().copied().max().unwrap_or(0).max(1);
+
+    let mut builder = ChartBuilder::on(area);
+    let mut chart = match options.axes {
+        Axes::RefQuery => builder
+            .margin_left(CHART_MARGIN)
+            .margin_right(CHART_MARGIN)
+            .margin_top(10)
+            .y_label_area_size(y_label_area(options.axes))
+            .build_cartesian_2d(ref_range.clone(), 0..max_count),
+        Axes::QueryRef => builder
+            .margin_top(CHART_MARGIN)
+            .margin_bottom(CHART_MARGIN)
+            .margin_right(CHART_MARGIN)
+            .margin_left(10)
+            .x_label_area_size(X_LABEL_AREA)
+            .build_cartesian_2d(0..max_count, ref_range.clone()),
+    }
+    .unwrap();
+
+    let foreground = options.theme.foreground();
+    chart
+        .configure_mesh()
+        .disable_mesh()
+        .x_labels(3)
+        .y_labels(3)
+        .axis_style(foreground)
+        .label_style(TextStyles::of(options.theme).axis_labels.clone())
+        .draw()
+        .unwrap();
+
+    let bars = counts
+        .iter()
+        .enumerate()
+        .filter(|(_, count)| **count > 0)
+        .map(|(bin, &count)| {
+            let start = ref_range.start + bin as u32 * bin_width;
+            let end = (start + bin_width).min(ref_range.end);
+            Rectangle::new(
+                [
+                    options.axes.point((start, 0)),
+                    options.axes.point((end, count)),
+                ],
+                color.mix(0.6).filled(),
+            )
+        });
+    chart.draw_series(bars).unwrap();
+}
+
+pub fn draw_chain<DB: DrawingBackend>(
+    root: DrawingArea<DB, Shift>,
+    read: &Read,
+    chain: &Chain,
+    options: &PlotOptions,
+) {
+    draw_chain_panel(&root, read, chain, options);
+    root.present().unwrap();
+}
+
+/// Rows and columns of the panel grid for a read, never wider than it has chains.
+fn panel_grid(read: &Read, columns: usize) -> (usize, usize) {
+    let columns = columns.clamp(1, read.chains.len().max(1));
+    (read.chains.len().div_ceil(columns).max(1), columns)
+}
+
+/// Draws every chain of a read as a tile of a grid `columns` wide.
+pub fn draw_panels<DB: DrawingBackend>(
+    root: DrawingArea<DB, Shift>,
+    read: &Read,
+    columns: usize,
+    options: &PlotOptions,
+) {
+    root.fill(&options.theme.background()).unwrap();
+    let (rows, columns) = panel_grid(read, columns);
+    for (chain_idx, (panel, chain)) in root
+        .split_evenly((rows, columns))
+        .iter()
+        .zip(&read.chains)
+        .enumerate()
+    {
+        if should_plot(read, chain, chain_idx, options) {
+            draw_chain_panel(panel, read, chain, options);
+        }
+    }
+    root.present().unwrap();
+}
+
+/// Size of each thumbnail of a contact sheet, label included, in pixels.
+const THUMBNAIL_SIZE: (u32, u32) = (400, 300);
+
+/// Height of the label under each thumbnail, in pixels.
+const THUMBNAIL_LABEL: u32 = 30;
+
+/// Background anchors drawn per thumbnail at most.
+const THUMBNAIL_ANCHORS: usize = 500;
+
+/// Rows and columns of a contact sheet: the smallest square grid that fits
+/// every chain, without empty rows.
+fn contact_sheet_grid(chains: usize) -> (usize, usize) {
+    let columns = (1..).find(|columns| columns * columns >= chains).unwrap();
+    (chains.div_ceil(columns).max(1), columns)
+}
+
+/// Draws a thumbnail of every chain of a read in a grid, each labelled with
+/// its score.
+pub fn draw_contact_sheet<DB: DrawingBackend>(
+    root: DrawingArea<DB, Shift>,
+    read: &Read,
+    options: &PlotOptions,
+) {
+    root.fill(&options.theme.background()).unwrap();
+    // Thumbnails are too small for anchor detail: draw a sample of the
+    // background anchors as points.
+    let options = &PlotOptions {
+        anchor_style: AnchorStyle::Points,
+        max_anchors: Some(
+            options
+                .max_anchors
+                .map_or(THUMBNAIL_ANCHORS, |max| max.min(THUMBNAIL_ANCHORS)),
+        ),
+        ..options.clone()
+    };
+    for (chain_idx, (thumbnail, chain)) in root
+        .split_evenly(contact_sheet_grid(read.chains.len()))
+        .iter()
+        .zip(&read.chains)
+        .enumerate()
+    {
+        if should_plot(read, chain, chain_idx, options) {
+            draw_thumbnail(thumbnail, read, chain_idx, chain, options);
+        }
+    }
+    root.present().unwrap();
+}
+
+/// A lightweight chain plot: anchors and the piecewise path on a bare chart,
+/// with the chain's score under it instead of a caption and legend.
+fn draw_thumbnail<DB: DrawingBackend>(
+    area: &DrawingArea<DB, Shift>,
+    read: &Read,
+    chain_idx: usize,
+    chain: &Chain,
+    options: &PlotOptions,
+) {
+    let colors = options.theme.colors(options.colors);
+    let styles = TextStyles::of(options.theme);
+    let (ref_plot_start, ref_plot_end) = chain_ref_range(read, chain, options);
+    let (width, height) = area.dim_in_pixel();
+    let (plot_area, label_area) = area.split_vertically(height.saturating_sub(THUMBNAIL_LABEL));
+
+    let ref_range = ref_plot_start..ref_plot_end;
+    let query_range = 0u32..read.read_len;
+    let mut builder = ChartBuilder::on(&plot_area);
+    builder
+        .margin(10)
+        .x_label_area_size(20)
+        .y_label_area_size(40);
+    let mut chart = match options.axes {
+        Axes::RefQuery => builder.build_cartesian_2d(ref_range, query_range),
+        Axes::QueryRef => builder.build_cartesian_2d(query_range, ref_range),
+    }
+    .unwrap();
+
+    let format_position = position_formatter(options.units, ref_plot_start, ref_plot_end);
+    let mut mesh = chart.configure_mesh();
+    mesh.disable_mesh()
+        .x_labels(3)
+        .y_labels(3)
+        .axis_style(options.theme.foreground())
+        .label_style(styles.axis_labels.clone());
+    match options.axes {
+        Axes::RefQuery => mesh.x_label_formatter(&format_position),
+        Axes::QueryRef => mesh.y_label_formatter(&format_position),
+    };
+    mesh.draw().unwrap();
+
+    let anchors = if chain.is_revcomp {
+        &read.rev_anchors
+    } else {
+        &read.fwd_anchors
+    };
+    draw_background_anchors(
+        &mut chart,
+        &background_segments(
+            read,
+            &anchors_in_range(read, anchors, ref_plot_start, ref_plot_end),
+            chain.is_revcomp,
+        ),
+        colors.background_anchors.color,
+        options,
+    );
+    let chain_color = match options.color_by {
+        ColorBy::Considered if chain.considered => colors.considered.color,
+        ColorBy::Considered => colors.unconsidered.color,
+        ColorBy::Score => score_color(read, chain, options.theme),
+    };
+    draw_chain_anchors(&mut chart, read, chain, chain_color.mix(0.5), options.axes);
+    if !options.mapping_only {
+        draw_path(
+            &mut chart,
+            &chain_segments(read, chain, &chain.cigar, chain.ref_start),
+            colors.piecewise.color.mix(0.5),
+            colors.piecewise.color.mix(0.9),
+            options,
+        );
+    }
+
+    label_area
+        .draw(&Text::new(
+            format!("Chain {chain_idx} {}", chain_legend(chain)),
+            (width as i32 / 2, 0),
+            styles.title.pos(Pos::new(HPos::Center, VPos::Top)),
+        ))
+        .unwrap();
+}
+
+/// Smallest and largest reference position of everything a chain's plot
+/// draws: its span, its anchors and, unless only the mapping is shown, both
+/// alignment paths.
+pub fn drawn_ref_extent(read: &Read, chain: &Chain, options: &PlotOptions) -> (u32, u32) {
+    let anchors = chain
+        .anchors
+        .iter()
+        .flat_map(|anchor| [anchor.ref_start, anchor.ref_start.saturating_add(read.k)]);
+    let paths = [
+        (&chain.cigar, chain.ref_start),
+        (&chain.ssw_cigar, chain.ssw_ref_start),
+    ]
+    .into_iter()
+    .filter(|(cigar, _)| !options.mapping_only && !cigar.is_empty())
+    .flat_map(|(cigar, ref_start)| parse_cigar_to_path(cigar, ref_start))
+    .map(|(ref_pos, _)| ref_pos);
+    chain
+        .rspan
+        .into_iter()
+        .chain(anchors)
+        .chain(paths)
+        .fold((u32::MAX, 0), |(min, max), pos| {
+            (min.min(pos), max.max(pos))
+        })
+}
+
+/// Reference range of a chain's plot: the padded extent of what it draws, or
+/// `options.ref_window`.
+fn chain_ref_range(read: &Read, chain: &Chain, options: &PlotOptions) -> (u32, u32) {
+    if let Some(window) = options.ref_window {
+        return window;
+    }
+    let (min_ref, max_ref) = drawn_ref_extent(read, chain, options);
+    let padding = plot_padding(read);
+    (
+        min_ref.saturating_sub(padding),
+        max_ref.saturating_add(padding),
+    )
+}
+
+/// Draws one chain's plot into `root` without presenting it, so the area can
+/// be a tile of a larger image.
+fn draw_chain_panel<DB: DrawingBackend>(
+    root: &DrawingArea<DB, Shift>,
+    read: &Read,
+    chain: &Chain,
+    options: &PlotOptions,
+) {
+    let mapping_only = options.mapping_only;
+    let colors = options.theme.colors(options.colors);
+    let background = colors.background_anchors.color;
+    let (ref_plot_start, ref_plot_end) = chain_ref_range(read, chain, options);
+
+    root.fill(&options.theme.background()).unwrap();
+
+    let title = match &options.title_template {
+        Some(template) => template.render(read, chain, &options.ref_names),
+        None if options.rich_caption => rich_title(chain, read, options),
+        None => default_title(read, chain, options),
+    };
+
+    let anchors_to_plot = if chain.is_revcomp {
+        &read.rev_anchors
+    } else {
+        &read.fwd_anchors
+    };
+    let filtered_anchors = anchors_in_range(read, anchors_to_plot, ref_plot_start, ref_plot_end);
+
+    let ref_range = ref_plot_start..ref_plot_end;
+    let chart_area;
+    let mut chart = if options.coverage_track {
+        let track;
+        (chart_area, track) = with_coverage_track(root, &title, options);
+        draw_coverage_track(
+            &track,
+            filtered_anchors.iter().copied().chain(&chain.anchors),
+            ref_range.clone(),
+            background,
+            options,
+        );
+        build_chart(&chart_area, None, options, ref_range, read)
+    } else if title.contains('\n') {
+        chart_area = titled(root, &title, options);
+        build_chart(&chart_area, None, options, ref_range, read)
+    } else {
+        build_chart(root, Some(&title), options, ref_range, read)
+    };
+
+    draw_mesh(&mut chart, options, ref_plot_start, ref_plot_end);
+    if options.diagonal
+        && let Some(segment) = diagonal_segment(read, chain, ref_plot_start, ref_plot_end)
+    {
+        chart
+            .draw_series(LineSeries::new(
+                options.axes.segment(segment),
+                options.theme.foreground().mix(0.5).stroke_width(1),
+            ))
+            .unwrap();
+    }
+
+    let clips = draw_clip_bands(
+        &mut chart,
+        read,
+        chain,
+        ref_plot_start..ref_plot_end,
+        options,
+    );
+
+    let step = draw_background_anchors(
+        &mut chart,
+        &background_segments(read, &filtered_anchors, chain.is_revcomp),
+        background,
+        options,
+    );
+
+    let chain_swatch = if chain.considered {
+        colors.considered
+    } else {
+        colors.unconsidered
+    };
+    let chain_color = match options.color_by {
+        ColorBy::Considered => chain_swatch.color.mix(0.5),
+        ColorBy::Score => score_color(read, chain, options.theme).mix(0.5),
+    };
+    draw_chain_anchors(&mut chart, read, chain, chain_color, options.axes);
+
+    if !mapping_only {
+        draw_path(
+            &mut chart,
+            &chain_segments(read, chain, &chain.cigar, chain.ref_start),
+            colors.piecewise.color.mix(0.5),
+            colors.piecewise.color.mix(0.9),
+            options,
+        );
+        draw_path(
+            &mut chart,
+            &chain_segments(read, chain, &chain.ssw_cigar, chain.ssw_ref_start),
+            colors.ssw.color.mix(0.5),
+            colors.ssw.color.mix(0.9),
+            options,
+        );
+    }
+    let highlighted = draw_highlighted_anchors(
+        &mut chart,
+        read,
+        chain.is_revcomp,
+        filtered_anchors.iter().copied().chain(&chain.anchors),
+        options,
+    );
+
+    legend_entry(
+        &mut chart,
+        background_label(&colors, step),
+        move |(x, y)| PathElement::new([(x, y), (x + 20, y)], background),
+    );
+
+    let chain_label = match options.color_by {
+        ColorBy::Considered => format!("{}: Chain {}", chain_swatch.name, chain_legend(chain)),
+        ColorBy::Score => format!("Chain {}", chain_legend(chain)),
+    };
+
+    legend_entry(&mut chart, chain_label, move |(x, y)| {
+        PathElement::new([(x, y), (x + 30, y)], chain_color.stroke_width(4))
+    });
+
+    if clips != (0, 0) {
+        let color = options.theme.foreground().mix(CLIP_SHADE).filled();
+        legend_entry(
+            &mut chart,
+            format!("Soft-clipped query: {}+{} bp", clips.0, clips.1),
+            move |(x, y)| Rectangle::new([(x, y - 5), (x + 30, y + 5)], color),
+        );
+    }
+
+    if highlighted && let Some(target) = &options.highlight_anchor {
+        let color = options.theme.foreground();
+        legend_entry(
+            &mut chart,
+            format!("Anchor near {}:{}", target.ref_start, target.query_start),
+            move |(x, y)| Circle::new((x + 15, y), 6, color.stroke_width(3)),
+        );
+    }
+
+    if !mapping_only {
+        let ssw_color = colors.ssw.color.mix(0.5);
+        let ssw_label = format!("{}: SSW path:         {}", colors.ssw.name, chain.ssw_cigar);
+        legend_entry(&mut chart, ssw_label, move |(x, y)| {
+            PathElement::new([(x, y), (x + 30, y)], ssw_color.stroke_width(4))
+        });
+
+        let piecewise_color = colors.piecewise.color.mix(0.5);
+        let piecewise_label = format!(
+            "{}: Piecewise path:   {}",
+            colors.piecewise.name, chain.cigar
+        );
+        legend_entry(&mut chart, piecewise_label, move |(x, y)| {
+            PathElement::new([(x, y), (x + 30, y)], piecewise_color.stroke_width(4))
+        });
+    }
+
+    if options.scale_bar {
+        draw_scale_bar(
+            &mut chart,
+            options,
+            ref_plot_start,
+            ref_plot_end,
+            read.read_len,
+        );
+    }
+
+    draw_legend(&mut chart, options.theme);
+    if options.color_by == ColorBy::Score {
+        draw_colorbar(root, read, options.theme);
+    }
+}
+
+pub fn draw_combined<DB: DrawingBackend>(
+    root: DrawingArea<DB, Shift>,
+    read: &Read,
+    options: &PlotOptions,
+) {
+    let colors = options.theme.colors(options.colors);
+    let background = colors.background_anchors.color;
+    let padding = plot_padding(read);
+    let (min_ref, max_ref) = read
+        .chains
+        .iter()
+        .map(|chain| drawn_ref_extent(read, chain, options))
+        .fold((u32::MAX, 0), |(min, max), (start, end)| {
+            (min.min(start), max.max(end))
+        });
+    let ref_plot_start = min_ref.saturating_sub(padding);
+    let ref_plot_end = max_ref.saturating_add(padding).max(ref_plot_start + 1);
+    let (ref_plot_start, ref_plot_end) =
+        options.ref_window.unwrap_or((ref_plot_start, ref_plot_end));
+
+    root.fill(&options.theme.background()).unwrap();
+
+    let title = format!(
+        "Read: {}, {} chains, Ref Span: {}-{}",
+        read.name,
+        read.chains.len(),
+        min_ref,
+        max_ref
+    );
+
+    let fwd_anchors = anchors_in_range(read, &read.fwd_anchors, ref_plot_start, ref_plot_end);
+    let rev_anchors = anchors_in_range(read, &read.rev_anchors, ref_plot_start, ref_plot_end);
+
+    let ref_range = ref_plot_start..ref_plot_end;
+    let chart_area;
+    let mut chart = if options.coverage_track {
+        let track;
+        (chart_area, track) = with_coverage_track(&root, &title, options);
+        let chain_anchors = read.chains.iter().flat_map(|chain| &chain.anchors);
+        draw_coverage_track(
+            &track,
+            fwd_anchors
+                .iter()
+                .chain(&rev_anchors)
+                .copied()
+                .chain(chain_anchors),
+            ref_range.clone(),
+            background,
+            options,
+        );
+        build_chart(&chart_area, None, options, ref_range, read)
+    } else {
+        build_chart(&root, Some(&title), options, ref_range, read)
+    };
+
+    draw_mesh(&mut chart, options, ref_plot_start, ref_plot_end);
+    let mut segments = background_segments(read, &fwd_anchors, false);
+    segments.extend(background_segments(read, &rev_anchors, true));
+    let step = draw_background_anchors(&mut chart, &segments, background, options);
+
+    legend_entry(
+        &mut chart,
+        background_label(&colors, step),
+        move |(x, y)| PathElement::new([(x, y), (x + 20, y)], background),
+    );
+
+    for (chain_idx, chain) in read.chains.iter().enumerate() {
+        let color = match options.color_by {
+            ColorBy::Considered => options.theme.series(colors.series(chain_idx)),
+            ColorBy::Score => score_color(read, chain, options.theme),
+        }
+        .mix(0.7);
+        draw_chain_anchors(&mut chart, read, chain, color, options.axes);
+        if !options.mapping_only {
+            draw_path(
+                &mut chart,
+                &chain_segments(read, chain, &chain.cigar, chain.ref_start),
+                color.mix(0.5),
+                color,
+                options,
+            );
+        }
+
+        let label = format!("Chain {chain_idx} {}", chain_legend(chain));
+        legend_entry(&mut chart, label, move |(x, y)| {
+            PathElement::new([(x, y), (x + 30, y)], color.stroke_width(4))
+        });
+    }
+
+    draw_legend(&mut chart, options.theme);
+    if options.color_by == ColorBy::Score {
+        draw_colorbar(&root, read, options.theme);
+    }
+
+    root.present().unwrap();
+}
@@ -0,0 +1,94 @@
+use std::{borrow::Cow, collections::HashMap, io, str::FromStr};
+
+use crate::input::read_input;
+
+/// Names and lengths of the reference sequences, keyed by the numeric
+/// `ref_id` of chains.
+#[derive(Debug, Clone, Default)]
+pub struct RefNames {
+    names: HashMap<u32, String>,
+    lengths: HashMap<u32, u64>,
+}
+
+/// Parses `<ref_id>\t<value>` lines; blank lines and `#` comments are skipped.
+fn parse_table<T: FromStr>(text: &str, column: &str) -> Result<HashMap<u32, T>, String> {
+    let mut table = HashMap::new();
+    for (line_idx, line) in text.lines().enumerate() {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (id, value) = line
+            .split_once('\t')
+            .ok_or_else(|| format!("line {}: expected <ref_id>\\t<{column}>", line_idx + 1))?;
+        let id = id
+            .trim()
+            .parse()
+            .map_err(|_| format!("line {}: invalid ref_id {id:?}", line_idx + 1))?;
+        let value = value
+            .trim()
+            .parse()
+            .map_err(|_| format!("line {}: invalid {column} {value:?}", line_idx + 1))?;
+        table.insert(id, value);
+    }
+    Ok(table)
+}
+
+fn load_table<T: FromStr>(path: &str, column: &str) -> io::Result<HashMap<u32, T>> {
+    let text = read_input(path)?;
+    parse_table(&text, column)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{path}: {e}")))
+}
+
+impl RefNames {
+    /// Parses `<ref_id>\t<name>` lines; blank lines and `#` comments are
+    /// skipped.
+    pub fn parse(text: &str) -> Result<RefNames, String> {
+        Ok(RefNames {
+            names: parse_table(text, "name")?,
+            lengths: HashMap::new(),
+        })
+    }
+
+    pub fn load(path: &str) -> io::Result<RefNames> {
+        Ok(RefNames {
+            names: load_table(path, "name")?,
+            lengths: HashMap::new(),
+        })
+    }
+
+    /// Adds the lengths of a `<ref_id>\t<length>` table, in the same format
+    /// as the names.
+    pub fn parse_lengths(&mut self, text: &str) -> Result<(), String> {
+        self.lengths = parse_table(text, "length")?;
+        Ok(())
+    }
+
+    pub fn load_lengths(&mut self, path: &str) -> io::Result<()> {
+        self.lengths = load_table(path, "length")?;
+        Ok(())
+    }
+
+    /// Name of a reference, or its numeric id when the table has none.
+    pub fn name(&self, ref_id: u32) -> Cow<'_, str> {
+        match self.names.get(&ref_id) {
+            Some(name) => Cow::Borrowed(name),
+            None => Cow::Owned(ref_id.to_string()),
+        }
+    }
+
+    /// Names a reference unless the table already names it.
+    pub fn insert(&mut self, ref_id: u32, name: String) {
+        self.names.entry(ref_id).or_insert(name);
+    }
+
+    /// Every named reference, in no particular order.
+    pub fn names(&self) -> impl Iterator<Item = (u32, &str)> {
+        self.names.iter().map(|(id, name)| (*id, name.as_str()))
+    }
+
+    /// Length of a reference, when the lengths table has it.
+    pub fn length(&self, ref_id: u32) -> Option<u64> {
+        self.lengths.get(&ref_id).copied()
+    }
+}
@@ -0,0 +1,167 @@
+use std::fmt;
+
+use crate::{Anchor, Read};
+
+/// Aggregate numbers over a set of parsed reads.
+#[derive(Debug, Clone, Default)]
+pub struct Stats {
+    pub reads: usize,
+    pub chains: usize,
+    pub mean_score: Option<f64>,
+    pub median_score: Option<f64>,
+    pub max_score: Option<f64>,
+    /// Reads with more than one chain.
+    pub multi_chain_reads: usize,
+    pub revcomp_chains: usize,
+    pub considered_chains: usize,
+    /// How the reported scores compare with ones recomputed from the anchors,
+    /// when asked for.
+    pub score_check: Option<ScoreCheck>,
+}
+
+impl Stats {
+    pub fn compute(reads: &[Read]) -> Stats {
+        let mut scores: Vec<f64> = reads
+            .iter()
+            .flat_map(|read| read.chains.iter().map(|chain| chain.score))
+            .collect();
+        scores.sort_by(f64::total_cmp);
+
+        let chains = scores.len();
+        let median_score = match chains {
+            0 => None,
+            _ if chains % 2 == 1 => Some(scores[chains / 2]),
+            _ => Some((scores[chains / 2 - 1] + scores[chains / 2]) / 2.0),
+        };
+        let all_chains = || reads.iter().flat_map(|read| &read.chains);
+
+        Stats {
+            reads: reads.len(),
+            chains,
+            mean_score: (chains > 0).then(|| scores.iter().sum::<f64>() / chains as f64),
+            median_score,
+            max_score: scores.last().copied(),
+            multi_chain_reads: reads.iter().filter(|read| read.chains.len() > 1).count(),
+            revcomp_chains: all_chains().filter(|chain| chain.is_revcomp).count(),
+            considered_chains: all_chains().filter(|chain| chain.considered).count(),
+            score_check: None,
+        }
+    }
+}
+
+/// Reported scores further than this fraction from the recomputed ones count
+/// as mismatched.
+const SCORE_TOLERANCE: f64 = 0.1;
+
+/// A colinear chaining score over anchors in chain order: `k` for the first
+/// anchor, then for each next one the query bases it adds, at most `k`, minus
+/// one per base the step drifts off the diagonal.
+pub fn chaining_score(anchors: &[Anchor], k: u32) -> f64 {
+    let Some(first) = anchors.first() else {
+        return 0.0;
+    };
+    let mut score = f64::from(k);
+    let mut prev = first;
+    for anchor in &anchors[1..] {
+        let query_step = anchor.query_start.abs_diff(prev.query_start);
+        let ref_step = anchor.ref_start.abs_diff(prev.ref_start);
+        let matched = query_step.min(ref_step).min(k);
+        let gap = query_step.abs_diff(ref_step);
+        score += f64::from(matched) - f64::from(gap);
+        prev = anchor;
+    }
+    score
+}
+
+/// Ratios of reported to recomputed chain scores. Chains whose recomputed
+/// score is not positive are left out.
+#[derive(Debug, Clone)]
+pub struct ScoreCheck {
+    pub chains: usize,
+    pub mean_ratio: f64,
+    pub min_ratio: f64,
+    pub max_ratio: f64,
+    /// Chains whose ratio is off 1 by more than `SCORE_TOLERANCE`.
+    pub mismatched: usize,
+}
+
+impl ScoreCheck {
+    /// Returns `None` when no chain has a positive recomputed score.
+    pub fn compute(reads: &[Read]) -> Option<ScoreCheck> {
+        let ratios: Vec<f64> = reads
+            .iter()
+            .flat_map(|read| {
+                read.chains.iter().filter_map(|chain| {
+                    let recomputed = chaining_score(&chain.anchors, read.k);
+                    (recomputed > 0.0).then(|| chain.score / recomputed)
+                })
+            })
+            .collect();
+        if ratios.is_empty() {
+            return None;
+        }
+        Some(ScoreCheck {
+            chains: ratios.len(),
+            mean_ratio: ratios.iter().sum::<f64>() / ratios.len() as f64,
+            min_ratio: ratios.iter().copied().fold(f64::INFINITY, f64::min),
+            max_ratio: ratios.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+            mismatched: ratios
+                .iter()
+                .filter(|ratio| (*ratio - 1.0).abs() > SCORE_TOLERANCE)
+                .count(),
+        })
+    }
+}
+
+fn fraction(count: usize, total: usize) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        count as f64 / total as f64
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let score = |score: Option<f64>| score.map_or("n/a".to_string(), |s| format!("{s:.2}"));
+
+        writeln!(f, "reads:              {}", self.reads)?;
+        writeln!(f, "chains:             {}", self.chains)?;
+        writeln!(f, "mean score:         {}", score(self.mean_score))?;
+        writeln!(f, "median score:       {}", score(self.median_score))?;
+        writeln!(f, "max score:          {}", score(self.max_score))?;
+        writeln!(
+            f,
+            "multi-chain reads:  {} ({:.1}%)",
+            self.multi_chain_reads,
+            fraction(self.multi_chain_reads, self.reads) * 100.0
+        )?;
+        writeln!(
+            f,
+            "revcomp chains:     {} ({:.1}%)",
+            self.revcomp_chains,
+            fraction(self.revcomp_chains, self.chains) * 100.0
+        )?;
+        write!(
+            f,
+            "considered chains:  {} considered, {} not considered",
+            self.considered_chains,
+            self.chains - self.considered_chains
+        )?;
+        if let Some(check) = &self.score_check {
+            write!(
+                f,
+                "\nscore ratio:        mean {:.2}, min {:.2}, max {:.2} over {} chains\n\
+                 mismatched scores:  {} ({:.1}%) off the recomputed score by more than {:.0}%",
+                check.mean_ratio,
+                check.min_ratio,
+                check.max_ratio,
+                check.chains,
+                check.mismatched,
+                fraction(check.mismatched, check.chains) * 100.0,
+                SCORE_TOLERANCE * 100.0
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,250 @@
+use std::{fmt, io, io::BufRead, sync::mpsc, thread};
+
+use crate::{
+    Read,
+    filter::ReadFilter,
+    parse::{PARSE_BATCH, ParseError, ParsedReads, QUERY_MARKER, ReadCollector, find, parse_reads},
+};
+
+/// Splits a buffered reader into `Query:`-delimited blocks without loading the
+/// whole input, yielding each block with its byte offset.
+pub struct QueryBlocks<R> {
+    reader: R,
+    buffer: Vec<u8>,
+    /// Start of the unconsumed part of `buffer`; the consumed prefix is only
+    /// dropped when the buffer is refilled, instead of shifting the buffer
+    /// after every block.
+    start: usize,
+    /// Offset of `buffer[start]` in the input.
+    offset: usize,
+    /// Length of the unconsumed part already searched for the next block start.
+    scanned: usize,
+    eof: bool,
+}
+
+impl<R: BufRead> QueryBlocks<R> {
+    pub fn new(reader: R) -> Self {
+        QueryBlocks {
+            reader,
+            buffer: Vec::new(),
+            start: 0,
+            offset: 0,
+            scanned: 0,
+            eof: false,
+        }
+    }
+
+    fn fill(&mut self) -> io::Result<()> {
+        let chunk = self.reader.fill_buf()?;
+        if chunk.is_empty() {
+            self.eof = true;
+        }
+        let len = chunk.len();
+        self.buffer.drain(..self.start);
+        self.start = 0;
+        self.buffer.extend_from_slice(chunk);
+        self.reader.consume(len);
+        Ok(())
+    }
+
+    fn pending(&self) -> &[u8] {
+        &self.buffer[self.start..]
+    }
+
+    fn discard(&mut self, len: usize) {
+        self.start += len;
+        self.offset += len;
+        self.scanned = 0;
+    }
+}
+
+impl<R: BufRead> Iterator for QueryBlocks<R> {
+    type Item = io::Result<(usize, Vec<u8>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if !self.pending().starts_with(QUERY_MARKER) {
+                match find(self.pending(), QUERY_MARKER) {
+                    Some(at) => self.discard(at),
+                    None => {
+                        // Keep a possible partial marker at the end of the buffer.
+                        let len = self.pending().len();
+                        let keep = (QUERY_MARKER.len() - 1).min(len);
+                        self.discard(len - keep);
+                        if self.eof {
+                            return None;
+                        }
+                        if let Err(e) = self.fill() {
+                            return Some(Err(e));
+                        }
+                        continue;
+                    }
+                }
+            }
+
+            let from = self.scanned.saturating_sub(QUERY_MARKER.len() - 1).max(1);
+            if let Some(at) = find(&self.pending()[from..], QUERY_MARKER) {
+                let start = self.offset;
+                let block = self.pending()[..from + at].to_vec();
+                self.discard(from + at);
+                return Some(Ok((start, block)));
+            }
+            self.scanned = self.pending().len();
+
+            if self.eof {
+                if self.pending().is_empty() {
+                    return None;
+                }
+                let start = self.offset;
+                let block = self.pending().to_vec();
+                self.discard(block.len());
+                return Some(Ok((start, block)));
+            }
+            if let Err(e) = self.fill() {
+                return Some(Err(e));
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum StreamError {
+    /// Reading the input failed; the stream cannot continue.
+    Io(io::Error),
+    /// A block was malformed and skipped; the stream continues with the next one.
+    Parse { block: usize, error: ParseError },
+}
+
+impl fmt::Display for StreamError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StreamError::Io(e) => write!(f, "{e}"),
+            StreamError::Parse { block, error } => write!(f, "block {block}: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for StreamError {}
+
+/// Parses reads one `Query:` block at a time from a buffered reader, with the
+/// same filtering and `-n` limit as `parse_file`.
+pub struct ReadStream<R> {
+    blocks: std::iter::Enumerate<QueryBlocks<R>>,
+    n: Option<usize>,
+    mapping_only: bool,
+    filter: ReadFilter,
+    /// Reads accepted by the filter so far, sampled or not.
+    accepted: usize,
+    parsed: usize,
+}
+
+impl<R: BufRead> ReadStream<R> {
+    pub fn new(reader: R, n: Option<usize>, mapping_only: bool, filter: ReadFilter) -> Self {
+        ReadStream {
+            blocks: QueryBlocks::new(reader).enumerate(),
+            n,
+            mapping_only,
+            filter,
+            accepted: 0,
+            parsed: 0,
+        }
+    }
+}
+
+impl<R: BufRead> ReadStream<R> {
+    /// Collects the reads and the malformed blocks skipped along the way, as
+    /// `parse_file` returns them. Reading stops as soon as `n` reads are
+    /// parsed, so a small limit never touches the rest of the input.
+    pub fn collect_reads(self) -> io::Result<ParsedReads> {
+        let mut reads = Vec::new();
+        let mut errors = Vec::new();
+        for item in self {
+            match item {
+                Ok(read) => reads.push(read),
+                Err(StreamError::Parse { block, error }) => errors.push((block, error)),
+                Err(StreamError::Io(e)) => return Err(e),
+            }
+        }
+        Ok((reads, errors))
+    }
+}
+
+impl<R: BufRead> Iterator for ReadStream<R> {
+    type Item = Result<Read, StreamError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.n.is_some_and(|max| self.parsed >= max) {
+            return None;
+        }
+        loop {
+            let (block_idx, block) = self.blocks.next()?;
+            let (start, block) = match block {
+                Ok(block) => block,
+                Err(e) => return Some(Err(StreamError::Io(e))),
+            };
+            match parse_reads(&block, self.mapping_only) {
+                Ok(Some(read)) if self.filter.keeps(&read) => {
+                    self.accepted += 1;
+                    if !self.filter.samples(self.accepted - 1) {
+                        continue;
+                    }
+                    self.parsed += 1;
+                    return Some(Ok(read));
+                }
+                Ok(_) => {}
+                Err(mut error) => {
+                    error.offset += start;
+                    return Some(Err(StreamError::Parse {
+                        block: block_idx,
+                        error,
+                    }));
+                }
+            }
+        }
+    }
+}
+
+/// Batches of `PARSE_BATCH` blocks the reader thread of `parse_reader` may
+/// queue ahead of the parsers, which caps the memory held by input that is
+/// read but not yet parsed.
+const QUEUED_BATCHES: usize = 2;
+
+/// Parses a dump like `parse_file`, reading it on a separate thread that sends
+/// batches of completed `Query:` blocks through a bounded channel. Each batch
+/// is parsed in parallel while the next one is read, so reading the input
+/// overlaps with parsing it. Reading stops once `n` reads are parsed.
+pub fn parse_reader<R: BufRead + Send>(
+    reader: R,
+    n: Option<usize>,
+    mapping_only: bool,
+    filter: &ReadFilter,
+) -> io::Result<ParsedReads> {
+    thread::scope(|scope| {
+        let (sender, receiver) = mpsc::sync_channel(QUEUED_BATCHES);
+        scope.spawn(move || {
+            let mut blocks = QueryBlocks::new(reader);
+            loop {
+                let batch = blocks
+                    .by_ref()
+                    .take(PARSE_BATCH)
+                    .collect::<io::Result<Vec<_>>>();
+                let done = batch
+                    .as_ref()
+                    .map_or(true, |batch| batch.len() < PARSE_BATCH);
+                // Sending fails once the parsers have stopped and dropped the
+                // receiver.
+                if sender.send(batch).is_err() || done {
+                    break;
+                }
+            }
+        });
+
+        let mut collector = ReadCollector::new(n, mapping_only, filter);
+        for batch in receiver {
+            if !collector.parse_batch(&batch?) {
+                break;
+            }
+        }
+        Ok(collector.finish())
+    })
+}
@@ -0,0 +1,114 @@
+use crate::{Chain, Read, cigar::identity, refs::RefNames};
+
+#[derive(Debug, Clone, Copy)]
+enum Field {
+    Name,
+    Score,
+    RefId,
+    RefStart,
+    RefEnd,
+    Strand,
+    Identity,
+    Coverage,
+    Mapq,
+}
+
+impl Field {
+    fn from_name(name: &str) -> Option<Field> {
+        Some(match name {
+            "name" => Field::Name,
+            "score" => Field::Score,
+            "ref_id" => Field::RefId,
+            "rstart" => Field::RefStart,
+            "rend" => Field::RefEnd,
+            "strand" => Field::Strand,
+            "identity" => Field::Identity,
+            "coverage" => Field::Coverage,
+            "mapq" => Field::Mapq,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Part {
+    Text(String),
+    Field(Field),
+}
+
+/// A plot caption with `{name}`, `{score}`, `{ref_id}`, `{rstart}`, `{rend}`,
+/// `{strand}`, `{identity}`, `{coverage}` and `{mapq}` placeholders, parsed
+/// once and filled in per chain. `{{` and `}}` stand for literal braces.
+#[derive(Debug, Clone)]
+pub struct TitleTemplate {
+    parts: Vec<Part>,
+}
+
+impl TitleTemplate {
+    pub fn parse(template: &str) -> Result<TitleTemplate, String> {
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut chars = template.chars();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.as_str().starts_with('{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.as_str().starts_with('}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let rest = chars.as_str();
+                    let end = rest
+                        .find('}')
+                        .ok_or_else(|| format!("unclosed placeholder in {template:?}"))?;
+                    let name = &rest[..end];
+                    let field = Field::from_name(name)
+                        .ok_or_else(|| format!("unknown placeholder {{{name}}}"))?;
+                    if !text.is_empty() {
+                        parts.push(Part::Text(std::mem::take(&mut text)));
+                    }
+                    parts.push(Part::Field(field));
+                    chars = rest[end + 1..].chars();
+                }
+                '}' => return Err(format!("unmatched '}}' in {template:?}")),
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            parts.push(Part::Text(text));
+        }
+        Ok(TitleTemplate { parts })
+    }
+
+    pub fn render(&self, read: &Read, chain: &Chain, ref_names: &RefNames) -> String {
+        let mut title = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => title.push_str(text),
+                Part::Field(field) => title.push_str(&match field {
+                    Field::Name => read.name.clone(),
+                    Field::Score => format!("{:.2}", chain.score),
+                    Field::RefId => ref_names.name(chain.ref_id).into_owned(),
+                    Field::RefStart => chain.rspan[0].to_string(),
+                    Field::RefEnd => chain.rspan[1].to_string(),
+                    Field::Strand => if chain.is_revcomp { "-" } else { "+" }.to_owned(),
+                    Field::Identity => identity(&chain.cigar)
+                        .map_or("n/a".to_owned(), |identity| {
+                            format!("{:.2}%", identity * 100.0)
+                        }),
+                    Field::Coverage => chain
+                        .query_coverage(read.read_len)
+                        .map_or("n/a".to_owned(), |coverage| {
+                            format!("{:.2}%", coverage * 100.0)
+                        }),
+                    Field::Mapq => chain.mapq.map_or("n/a".to_owned(), |mapq| mapq.to_string()),
+                }),
+            }
+        }
+        title
+    }
+}
@@ -0,0 +1,177 @@
+use std::{cmp::Ordering, collections::HashSet, fmt};
+
+use crate::{
+    Anchor, Read,
+    cigar::{query_length_discrepancy, validate_cigar},
+    refs::RefNames,
+};
+
+/// A consistency problem found in a parsed read.
+#[derive(Debug, Clone)]
+pub struct Warning {
+    pub read: String,
+    pub chain: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.chain {
+            Some(idx) => write!(f, "read {} chain {}: {}", self.read, idx, self.message),
+            None => write!(f, "read {}: {}", self.read, self.message),
+        }
+    }
+}
+
+/// Runs every check that applies to a read: those of `check_read`, the
+/// declared reference lengths and, when `cigar_count` is set, the number of
+/// CIGARs against the number of chains.
+pub fn check_all(read: &Read, ref_names: &RefNames, cigar_count: bool) -> Vec<Warning> {
+    let mut warnings = check_read(read);
+    if cigar_count {
+        warnings.extend(check_cigar_count(read));
+    }
+    warnings.extend(check_ref_lengths(read, ref_names));
+    warnings
+}
+
+/// Runs every consistency check on a read that needs nothing but the read.
+pub fn check_read(read: &Read) -> Vec<Warning> {
+    let strand_anchors = |anchors: &[Anchor]| -> HashSet<(u32, u32)> {
+        anchors
+            .iter()
+            .map(|anchor| (anchor.ref_start, anchor.query_start))
+            .collect()
+    };
+    let fwd_anchors = strand_anchors(&read.fwd_anchors);
+    let rev_anchors = strand_anchors(&read.rev_anchors);
+
+    let mut warnings = Vec::new();
+    let background = read.fwd_anchors.len() + read.rev_anchors.len();
+    let overrunning = read
+        .fwd_anchors
+        .iter()
+        .chain(&read.rev_anchors)
+        .filter(|anchor| anchor.overruns(read.k, read.read_len))
+        .count();
+    if overrunning > 0 {
+        warnings.push(Warning {
+            read: read.name.clone(),
+            chain: None,
+            message: format!(
+                "{overrunning} of {background} anchors run past the end of the read \
+                 (query_start + k > {})",
+                read.read_len
+            ),
+        });
+    }
+
+    for (idx, chain) in read.chains.iter().enumerate() {
+        let mut warn = |message: String| {
+            warnings.push(Warning {
+                read: read.name.clone(),
+                chain: Some(idx),
+                message,
+            })
+        };
+
+        let [query_start, query_end] = chain.qspan;
+        if query_start >= query_end {
+            warn(format!(
+                "query span {query_start}-{query_end} is empty or inverted"
+            ));
+        }
+        if query_end > read.read_len {
+            warn(format!(
+                "query span {query_start}-{query_end} ends past the read length {}",
+                read.read_len
+            ));
+        }
+
+        let [ref_start, ref_end] = chain.rspan;
+        if ref_start >= ref_end {
+            warn(format!(
+                "reference span {ref_start}-{ref_end} is empty or inverted"
+            ));
+        }
+
+        let cigar_valid = validate_cigar(&chain.cigar);
+        if let Err(e) = &cigar_valid {
+            warn(format!("invalid CIGAR {}: {e}", chain.cigar));
+        }
+        if let Err(e) = validate_cigar(&chain.ssw_cigar) {
+            warn(format!("invalid SSW CIGAR {}: {e}", chain.ssw_cigar));
+        }
+
+        if !chain.cigar.is_empty() && cigar_valid.is_ok() {
+            let discrepancy = query_length_discrepancy(&chain.cigar, read.read_len);
+            if discrepancy != 0 {
+                warn(format!(
+                    "CIGAR {} covers {} query bases but the read has {} ({:+})",
+                    chain.cigar,
+                    i64::from(read.read_len) + discrepancy,
+                    read.read_len,
+                    discrepancy
+                ));
+            }
+        }
+
+        let (expected, strand) = if chain.is_revcomp {
+            (&rev_anchors, "reverse")
+        } else {
+            (&fwd_anchors, "forward")
+        };
+        let missing = chain
+            .anchors
+            .iter()
+            .filter(|anchor| !expected.contains(&(anchor.ref_start, anchor.query_start)))
+            .count();
+        if missing > 0 {
+            warn(format!(
+                "{missing} of {} anchors are not among the {strand}-strand anchors",
+                chain.anchors.len()
+            ));
+        }
+    }
+    warnings
+}
+
+/// Reports a `Cigars` block whose length differs from the `Chains` block's.
+/// CIGARs are paired with chains by position, so fewer CIGARs leave the
+/// trailing chains without one and extra CIGARs are dropped. Reads without
+/// parsed CIGARs are not checked.
+pub fn check_cigar_count(read: &Read) -> Option<Warning> {
+    let cigars = read.cigar_count?;
+    let chains = read.chains.len();
+    let consequence = match cigars.cmp(&chains) {
+        Ordering::Equal => return None,
+        Ordering::Less => format!("the last {} chains have no CIGAR", chains - cigars),
+        Ordering::Greater => format!("the last {} CIGARs are ignored", cigars - chains),
+    };
+    Some(Warning {
+        read: read.name.clone(),
+        chain: None,
+        message: format!("{cigars} CIGARs for {chains} chains; {consequence}"),
+    })
+}
+
+/// Reports chains whose reference span ends past the declared length of their
+/// reference. References without a declared length are not checked.
+pub fn check_ref_lengths(read: &Read, ref_names: &RefNames) -> Vec<Warning> {
+    read.chains
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, chain)| {
+            let length = ref_names.length(chain.ref_id)?;
+            (u64::from(chain.rspan[1]) > length).then(|| Warning {
+                read: read.name.clone(),
+                chain: Some(idx),
+                message: format!(
+                    "reference span ends at {}, past the {length} bases of reference {}",
+                    chain.rspan[1],
+                    ref_names.name(chain.ref_id)
+                ),
+            })
+        })
+        .collect()
+}
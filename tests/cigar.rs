@@ -0,0 +1,165 @@
+use extract_chains::cigar::{
+    CigarError, GapStats, cigar_distance, gap_stats, normalize_cigar, paf_counts,
+    parse_cigar_to_path, parse_cigar_to_segments, soft_clips, strip_clips, validate_cigar,
+};
+
+#[test]
+fn matches_move_along_the_diagonal() {
+    assert_eq!(
+        parse_cigar_to_path("100M", 1000),
+        vec![(1000, 0), (1100, 100)]
+    );
+}
+
+#[test]
+fn insertions_only_advance_the_query() {
+    assert_eq!(
+        parse_cigar_to_path("50M10I50M", 1000),
+        vec![(1000, 0), (1050, 50), (1050, 60), (1100, 110)]
+    );
+}
+
+#[test]
+fn deletions_only_advance_the_reference() {
+    assert_eq!(
+        parse_cigar_to_path("50M10D50M", 1000),
+        vec![(1000, 0), (1050, 50), (1060, 50), (1110, 100)]
+    );
+}
+
+#[test]
+fn soft_clips_advance_the_query() {
+    assert_eq!(
+        parse_cigar_to_path("5S90M5S", 1000),
+        vec![(1000, 0), (1000, 5), (1090, 95), (1090, 100)]
+    );
+}
+
+#[test]
+fn skips_only_advance_the_reference() {
+    assert_eq!(
+        parse_cigar_to_path("50M1000N50M", 1000),
+        vec![(1000, 0), (1050, 50), (2050, 50), (2100, 100)]
+    );
+}
+
+#[test]
+fn empty_cigar_is_just_the_start() {
+    assert_eq!(parse_cigar_to_path("", 1000), vec![(1000, 0)]);
+}
+
+#[test]
+fn hard_clips_do_not_move_the_path() {
+    assert_eq!(
+        parse_cigar_to_path("10H50M5H", 100),
+        parse_cigar_to_path("50M", 100)
+    );
+}
+
+#[test]
+fn padding_does_not_move_the_path() {
+    assert_eq!(
+        parse_cigar_to_path("20M3P30M", 0),
+        vec![(0, 0), (20, 20), (50, 50)]
+    );
+}
+
+#[test]
+fn validate_cigar_rejects_malformed_strings() {
+    assert_eq!(validate_cigar("10M2I5D"), Ok(()));
+    assert_eq!(
+        validate_cigar("10M2Q"),
+        Err(CigarError::UnknownOp { op: 'Q', offset: 4 })
+    );
+    assert_eq!(
+        validate_cigar("10MM"),
+        Err(CigarError::MissingCount { op: 'M', offset: 3 })
+    );
+    assert_eq!(
+        validate_cigar("10M5"),
+        Err(CigarError::MissingOp { offset: 3 })
+    );
+}
+
+#[test]
+fn long_cigars_saturate_instead_of_overflowing() {
+    assert_eq!(
+        parse_cigar_to_path("4000000000M4000000000I", 1000),
+        vec![
+            (1000, 0),
+            (4_000_001_000, 4_000_000_000),
+            (4_000_001_000, u32::MAX)
+        ]
+    );
+    assert_eq!(
+        parse_cigar_to_path("10M", u32::MAX - 5),
+        vec![(u32::MAX - 5, 0), (u32::MAX, 10)]
+    );
+    let stats = gap_stats("3000000000D3000000000D");
+    assert_eq!((stats.deletions, stats.deleted_bases), (2, u32::MAX));
+}
+
+#[test]
+fn gap_stats_counts_insertions_and_deletions() {
+    assert_eq!(
+        gap_stats("5S40M30D10M25I2I25M"),
+        GapStats {
+            insertions: 2,
+            inserted_bases: 27,
+            deletions: 1,
+            deleted_bases: 30,
+            largest_gap: 30,
+        }
+    );
+    assert_eq!(gap_stats(""), GapStats::default());
+    assert_eq!(gap_stats("10S"), GapStats::default());
+}
+
+#[test]
+fn paf_counts_skip_clips_and_introns() {
+    assert_eq!(paf_counts("5S40=2X10M3I4D100N10=5H"), (60, 69));
+    assert_eq!(strip_clips("5S40=2X10M5H"), "40=2X10M");
+}
+
+#[test]
+fn cigar_distance_compares_normalized_runs() {
+    assert_eq!(cigar_distance("5M5M", "10M", false), 0);
+    assert_eq!(cigar_distance("10M2D10M", "10M2I10M", false), 1);
+    assert_eq!(cigar_distance("", "10M", false), 1);
+    assert_eq!(cigar_distance("50=2X48=", "100M", false), 3);
+    assert_eq!(cigar_distance("50=2X48=", "100M", true), 0);
+    assert_eq!(cigar_distance("50=2X48=", "50M2I48M", true), 3);
+}
+
+#[test]
+fn cigar_distance_tells_mismatch_placement_apart() {
+    assert_eq!(cigar_distance("10=", "5=1X4=", false), 3);
+    assert_eq!(cigar_distance("4=1X5=", "5=1X4=", false), 2);
+    assert_eq!(cigar_distance("10=", "5=1X4=", true), 0);
+}
+
+#[test]
+fn normalize_cigar_merges_runs_and_drops_empty_ops() {
+    assert_eq!(normalize_cigar("10M5M"), "15M");
+    assert_eq!(normalize_cigar("0M10M"), "10M");
+    assert_eq!(normalize_cigar("5S10M0I5M2I3I4D5="), "5S15M5I4D5=");
+    assert_eq!(normalize_cigar("10=2X"), "10=2X");
+    assert_eq!(normalize_cigar("0M"), "");
+}
+
+#[test]
+fn segments_of_split_runs_match_the_normalized_cigar() {
+    assert_eq!(
+        parse_cigar_to_segments("10M5M3I0D2I", 100),
+        parse_cigar_to_segments("15M5I", 100)
+    );
+}
+
+#[test]
+fn soft_clips_are_read_from_both_ends_inside_hard_clips() {
+    assert_eq!(soft_clips("10S40M5S"), (10, 5));
+    assert_eq!(soft_clips("3H2S4S40M5S2H"), (6, 5));
+    assert_eq!(soft_clips("40M2I5M"), (0, 0));
+    assert_eq!(soft_clips("20S"), (20, 0));
+    assert_eq!(soft_clips(""), (0, 0));
+}
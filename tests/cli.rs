@@ -0,0 +1,71 @@
+use std::process::Command;
+
+const DUMP: &str = "\
+Query: read1
+L=100,k=15
+Anchors for forward strand [{1000,0}]
+Anchors for reverse strand []
+Chains[{ref_id=0,score=4x2.5,query_start=0,query_end=65,ref_start=1000,ref_end=1065,is_revcomp=false,anchors=[{1000,0}]}]
+Cigars:[(65M,was_considered=1,rstart=1000,ssw=65M,ssw_rstart=1000)]
+Done!
+Query: read2
+L=50,k=11
+Anchors for forward strand [{200,0}]
+Anchors for reverse strand []
+Chains[{ref_id=3,score=15,query_start=0,query_end=15,ref_start=200,ref_end=215,is_revcomp=false,anchors=[{200,0}]}]
+Cigars:[(50M,was_considered=1,rstart=200,ssw=50M,ssw_rstart=200)]
+Done!
+";
+
+/// Runs the binary on `DUMP` with `args`, in a directory of its own that also
+/// holds `config.toml` with `config`, returning the exit status and output.
+fn run(test: &str, config: &str, args: &[&str]) -> (bool, String, String) {
+    let dir =
+        std::env::temp_dir().join(format!("extract_chains_cli_{test}_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("dump.txt"), DUMP).unwrap();
+    std::fs::write(dir.join("config.toml"), config).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_extract_chains"))
+        .current_dir(&dir)
+        .args(["dump.txt", "--config", "config.toml", "-o", "plots"])
+        .args(args)
+        .env_remove("RUST_LOG")
+        .output()
+        .unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+    (
+        output.status.success(),
+        String::from_utf8(output.stdout).unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+    )
+}
+
+#[test]
+fn skipped_blocks_are_summarised_at_the_default_verbosity() {
+    let (success, _, stderr) = run("summary", "", &["--dry-run"]);
+    assert!(success, "{stderr}");
+    assert!(
+        stderr
+            .lines()
+            .any(|line| line == "warning: parsed 1 reads, skipped 1 malformed blocks"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn config_sets_the_read_limit_and_dry_run() {
+    let (success, stdout, stderr) = run("config_n", "n = 0\ndry_run = true\n", &[]);
+    assert!(success, "{stderr}");
+    assert!(
+        stdout.contains("would write 0 images for 0 reads under plots"),
+        "{stdout}"
+    );
+}
+
+#[test]
+fn config_rejects_unknown_keys() {
+    let (success, _, stderr) = run("config_typo", "min_scroe = 3\n", &["--dry-run"]);
+    assert!(!success);
+    assert!(stderr.contains("unknown field `min_scroe`"), "{stderr}");
+}
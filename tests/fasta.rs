@@ -0,0 +1,19 @@
+use extract_chains::fasta::{IndexedFasta, reverse_complement};
+
+#[test]
+fn fetch_spans_line_breaks_and_clamps_to_the_contig() {
+    let path = std::env::temp_dir().join(format!("extract_chains_fetch_{}.fa", std::process::id()));
+    std::fs::write(&path, ">chr1 first contig\nACGTA\nCCGGT\nTT\n>chr2\nGGGG\n").unwrap();
+    let mut fasta = IndexedFasta::open(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(fasta.fetch("chr1", 3, 8).unwrap().unwrap(), b"TACCG");
+    assert_eq!(fasta.fetch("chr1", 10, 20).unwrap().unwrap(), b"TT");
+    assert_eq!(fasta.fetch("chr2", 1, 3).unwrap().unwrap(), b"GG");
+    assert!(fasta.fetch("chr3", 0, 1).unwrap().is_none());
+}
+
+#[test]
+fn reverse_complement_keeps_case_and_unknown_bases() {
+    assert_eq!(reverse_complement(b"ACGTNacgt"), b"acgtNACGT");
+}
@@ -0,0 +1,9 @@
+use extract_chains::overlap::overlap_fraction;
+
+#[test]
+fn overlap_is_measured_against_the_shorter_span() {
+    assert_eq!(overlap_fraction([0, 100], [50, 100]), 1.0);
+    assert_eq!(overlap_fraction([0, 100], [80, 120]), 0.5);
+    assert_eq!(overlap_fraction([0, 50], [50, 100]), 0.0);
+    assert_eq!(overlap_fraction([10, 10], [0, 100]), 0.0);
+}
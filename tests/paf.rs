@@ -0,0 +1,35 @@
+use extract_chains::{filter::ReadFilter, paf::parse_paf, refs::RefNames};
+
+const RECORD: &str = "read1\t100\t0\t60\t+\tchr2\t5000\t1000\t1060\t60\t60\t60\tcg:Z:60M\n";
+
+#[test]
+fn inverted_query_span_is_skipped() {
+    let inverted = RECORD.replace("\t0\t60\t+", "\t60\t0\t+");
+    let paf = format!("{inverted}{RECORD}");
+    let mut ref_names = RefNames::default();
+    let (reads, errors) = parse_paf(paf.as_bytes(), None, &ReadFilter::default(), &mut ref_names);
+
+    assert_eq!(reads.len(), 1);
+    assert_eq!(errors.len(), 1);
+    let (line, error) = &errors[0];
+    assert_eq!(*line, 0);
+    assert_eq!(error.field, "query end");
+    assert_eq!(error.offset, inverted.find("\t0\t+").unwrap() + 1);
+    assert!(
+        error
+            .to_string()
+            .ends_with(": query span 60-0 is inverted or runs past the 100 bases of the query")
+    );
+}
+
+#[test]
+fn target_names_are_kept_and_matched_to_known_ids() {
+    let paf = format!("{RECORD}{}", RECORD.replace("chr2", "chr7"));
+    let mut ref_names = RefNames::parse("4\tchr2\n").unwrap();
+    let (reads, errors) = parse_paf(paf.as_bytes(), None, &ReadFilter::default(), &mut ref_names);
+
+    assert!(errors.is_empty());
+    let ref_ids: Vec<_> = reads[0].chains.iter().map(|chain| chain.ref_id).collect();
+    assert_eq!(ref_ids, [4, 5]);
+    assert_eq!(ref_names.name(5), "chr7");
+}
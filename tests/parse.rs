@@ -0,0 +1,290 @@
+use std::{
+    io::{self, BufReader, Read},
+    num::NonZeroUsize,
+};
+
+use extract_chains::{
+    filter::{ReadFilter, parse_read_names},
+    parse::{parse_file, parse_reads_iter},
+    stream::{ReadStream, parse_reader},
+};
+
+const DUMP: &str = "\
+Query: read1
+L=100,k=15
+Anchors for forward strand [{1000,0}{1020,20}{1050,50}]
+Anchors for reverse strand [{5000,5}]
+Chains[{ref_id=0,score=42.5,query_start=0,query_end=65,ref_start=1000,ref_end=1065,is_revcomp=false,anchors=[{1000,0}{1020,20}{1050,50}]}{ref_id=1,score=10,query_start=5,query_end=20,ref_start=5000,ref_end=5015,is_revcomp=true,anchors=[{5000,5}]}]
+Cigars:[(50=2X48=,was_considered=1,rstart=1000,ssw=50M2I48M,ssw_rstart=998)(15M,was_considered=0,rstart=5000,ssw=15M,ssw_rstart=5001)]
+Done!
+Query: read2
+L=50,k=11
+Anchors for forward strand [{200,0}]
+Anchors for reverse strand []
+Chains[{ref_id=3,score=15,query_start=0,query_end=15,ref_start=200,ref_end=215,is_revcomp=false,anchors=[{200,0}]}]
+Cigars:[(50M,was_considered=1,rstart=200,ssw=50M,ssw_rstart=200)]
+Done!
+";
+
+#[test]
+fn parses_reads_chains_and_cigars() {
+    let (reads, errors) = parse_file(DUMP.as_bytes(), None, false, &ReadFilter::default());
+    assert!(errors.is_empty());
+    assert_eq!(reads.len(), 2);
+
+    let read = &reads[0];
+    assert_eq!(read.name, "read1");
+    assert_eq!(read.read_len, 100);
+    assert_eq!(read.k, 15);
+    let fwd: Vec<_> = read
+        .fwd_anchors
+        .iter()
+        .map(|a| (a.ref_start, a.query_start))
+        .collect();
+    assert_eq!(fwd, [(1000, 0), (1020, 20), (1050, 50)]);
+    let rev: Vec<_> = read
+        .rev_anchors
+        .iter()
+        .map(|a| (a.ref_start, a.query_start))
+        .collect();
+    assert_eq!(rev, [(5000, 5)]);
+
+    assert_eq!(read.chains.len(), 2);
+    let chain = &read.chains[0];
+    assert_eq!(chain.ref_id, 0);
+    assert_eq!(chain.score, 42.5);
+    assert_eq!(chain.mapq, None);
+    assert_eq!(chain.qspan, [0, 65]);
+    assert_eq!(chain.rspan, [1000, 1065]);
+    assert!(!chain.is_revcomp);
+    assert_eq!(chain.anchors.len(), 3);
+    assert_eq!(chain.cigar, "50=2X48=");
+    assert_eq!(chain.ref_start, 1000);
+    assert!(chain.considered);
+    assert_eq!(chain.ssw_cigar, "50M2I48M");
+    assert_eq!(chain.ssw_ref_start, 998);
+
+    let chain = &read.chains[1];
+    assert_eq!(chain.ref_id, 1);
+    assert_eq!(chain.score, 10.0);
+    assert_eq!(chain.qspan, [5, 20]);
+    assert_eq!(chain.rspan, [5000, 5015]);
+    assert!(chain.is_revcomp);
+    assert_eq!(chain.cigar, "15M");
+    assert!(!chain.considered);
+    assert_eq!(chain.ssw_ref_start, 5001);
+
+    let read = &reads[1];
+    assert_eq!(read.name, "read2");
+    assert_eq!(read.read_len, 50);
+    assert_eq!(read.k, 11);
+    assert!(read.rev_anchors.is_empty());
+    assert_eq!(read.chains[0].ref_id, 3);
+}
+
+#[test]
+fn mapping_only_skips_cigars_and_considers_the_first_chain() {
+    let (reads, errors) = parse_file(DUMP.as_bytes(), None, true, &ReadFilter::default());
+    assert!(errors.is_empty());
+    let chains = &reads[0].chains;
+    assert!(
+        chains
+            .iter()
+            .all(|c| c.cigar.is_empty() && c.ssw_cigar.is_empty())
+    );
+    assert!(chains[0].considered);
+    assert!(!chains[1].considered);
+}
+
+#[test]
+fn limit_stops_after_n_reads() {
+    let (reads, _) = parse_file(DUMP.as_bytes(), Some(1), false, &ReadFilter::default());
+    assert_eq!(reads.len(), 1);
+    assert_eq!(reads[0].name, "read1");
+}
+
+#[test]
+fn malformed_block_is_reported_and_skipped() {
+    let dump = DUMP.replacen("score=42.5", "score=4x2.5", 1);
+    let (reads, errors) = parse_file(dump.as_bytes(), None, false, &ReadFilter::default());
+
+    assert_eq!(reads.len(), 1);
+    assert_eq!(reads[0].name, "read2");
+    assert_eq!(errors.len(), 1);
+    let (block, error) = &errors[0];
+    assert_eq!(*block, 0);
+    assert_eq!(error.field, "score");
+    assert_eq!(&dump[error.offset..error.offset + 6], "4x2.5,");
+}
+
+#[test]
+fn iterator_yields_reads_and_errors_in_file_order() {
+    let names: Vec<_> = parse_reads_iter(DUMP.as_bytes())
+        .map(|read| read.unwrap().name)
+        .collect();
+    assert_eq!(names, ["read1", "read2"]);
+
+    let dump = DUMP.replacen("score=42.5", "score=4x2.5", 1);
+    let mut reads = parse_reads_iter(dump.as_bytes());
+    let error = reads.next().unwrap().unwrap_err();
+    assert_eq!(error.field, "score");
+    assert_eq!(&dump[error.offset..error.offset + 6], "4x2.5,");
+    assert_eq!(reads.next().unwrap().unwrap().name, "read2");
+    assert!(reads.next().is_none());
+}
+
+#[test]
+fn mapq_is_read_when_present() {
+    let dump = DUMP.replacen("score=42.5,", "score=42.5,mapq=60,", 1);
+    let (reads, errors) = parse_file(dump.as_bytes(), None, false, &ReadFilter::default());
+    assert!(errors.is_empty());
+    assert_eq!(reads[0].chains[0].mapq, Some(60));
+    assert_eq!(reads[0].chains[1].mapq, None);
+}
+
+#[test]
+fn read_length_range_filters_reads() {
+    let filter = ReadFilter {
+        min_read_len: Some(60),
+        ..ReadFilter::default()
+    };
+    let (reads, _) = parse_file(DUMP.as_bytes(), None, false, &filter);
+    assert_eq!(reads.len(), 1);
+    assert_eq!(reads[0].name, "read1");
+
+    let filter = ReadFilter {
+        max_read_len: Some(60),
+        ..ReadFilter::default()
+    };
+    let (reads, _) = parse_file(DUMP.as_bytes(), None, false, &filter);
+    assert_eq!(reads.len(), 1);
+    assert_eq!(reads[0].name, "read2");
+}
+
+#[test]
+fn scores_accept_signs_and_exponents_but_not_nan_or_infinity() {
+    for (score, expected) in [("-12", -12.0), ("1.2e3", 1200.0), ("+4.5E-1", 0.45)] {
+        let dump = DUMP.replacen("score=42.5", &format!("score={score}"), 1);
+        let (reads, errors) = parse_file(dump.as_bytes(), None, false, &ReadFilter::default());
+        assert!(errors.is_empty(), "{score}");
+        assert_eq!(reads[0].chains[0].score, expected);
+    }
+
+    for score in ["NaN", "inf", "-infinity"] {
+        let dump = DUMP.replacen("score=42.5", &format!("score={score}"), 1);
+        let (reads, errors) = parse_file(dump.as_bytes(), None, false, &ReadFilter::default());
+        assert_eq!(reads.len(), 1, "{score}");
+        assert_eq!(errors[0].1.field, "score");
+        assert!(dump[errors[0].1.offset..].starts_with(score));
+        assert!(
+            errors[0].1.to_string().ends_with(": score must be finite"),
+            "{score}"
+        );
+    }
+}
+
+/// A reader that fails, standing in for the part of a file that must not be read.
+struct Unreadable;
+
+impl Read for Unreadable {
+    fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+        Err(io::Error::other("read past the limit"))
+    }
+}
+
+#[test]
+fn limited_stream_stops_reading_after_n_reads() {
+    let input = BufReader::new(DUMP.as_bytes().chain(Unreadable));
+    let (reads, errors) = ReadStream::new(input, Some(1), false, ReadFilter::default())
+        .collect_reads()
+        .unwrap();
+    assert!(errors.is_empty());
+    assert_eq!(reads.len(), 1);
+    assert_eq!(reads[0].name, "read1");
+}
+
+#[test]
+fn crlf_line_endings_parse_like_lf() {
+    let crlf = DUMP.replace('\n', "\r\n");
+    let (lf_reads, _) = parse_file(DUMP.as_bytes(), None, false, &ReadFilter::default());
+    let (crlf_reads, errors) = parse_file(crlf.as_bytes(), None, false, &ReadFilter::default());
+
+    assert!(errors.is_empty());
+    assert_eq!(format!("{crlf_reads:?}"), format!("{lf_reads:?}"));
+    assert_eq!(crlf_reads[0].name, "read1");
+}
+
+#[test]
+fn sample_keeps_every_kth_read() {
+    let dump = DUMP.repeat(3);
+    let filter = ReadFilter {
+        sample: NonZeroUsize::new(2),
+        ..ReadFilter::default()
+    };
+    let (reads, _) = parse_file(dump.as_bytes(), None, false, &filter);
+    let names: Vec<_> = reads.iter().map(|read| read.name.as_str()).collect();
+    assert_eq!(names, ["read1", "read1", "read1"]);
+
+    let (streamed, _) = ReadStream::new(dump.as_bytes(), Some(2), false, filter)
+        .collect_reads()
+        .unwrap();
+    assert_eq!(streamed.len(), 2);
+}
+
+#[test]
+fn reader_thread_parses_like_parse_file() {
+    let dump = DUMP.replacen("score=42.5", "score=4x2.5", 1).repeat(3000);
+    let filter = ReadFilter::default();
+    let (expected_reads, expected_errors) = parse_file(dump.as_bytes(), None, false, &filter);
+    let (reads, errors) = parse_reader(dump.as_bytes(), None, false, &filter).unwrap();
+
+    assert_eq!(reads.len(), 3000);
+    assert_eq!(format!("{reads:?}"), format!("{expected_reads:?}"));
+    assert_eq!(format!("{errors:?}"), format!("{expected_errors:?}"));
+    assert_eq!(errors.last().unwrap().0, 2 * 2999);
+
+    let input = BufReader::new(dump.as_bytes().chain(Unreadable));
+    let (reads, _) = parse_reader(input, Some(10), false, &filter).unwrap();
+    assert_eq!(reads.len(), 10);
+    assert!(parse_reader(BufReader::new(Unreadable), None, false, &filter).is_err());
+}
+
+#[test]
+fn reads_file_names_compose_with_the_name_regex() {
+    let names = parse_read_names("read2\tfrom the assembly\n\nmissing\n");
+    assert_eq!(names.len(), 2);
+    let filter = ReadFilter {
+        names: Some(names),
+        ..ReadFilter::default()
+    };
+    let (reads, _) = parse_file(DUMP.as_bytes(), None, false, &filter);
+    let kept: Vec<_> = reads.iter().map(|read| read.name.as_str()).collect();
+    assert_eq!(kept, ["read2"]);
+
+    let filter = ReadFilter {
+        name_regex: Some(regex::Regex::new("1$").unwrap()),
+        ..filter
+    };
+    assert!(
+        parse_file(DUMP.as_bytes(), None, false, &filter)
+            .0
+            .is_empty()
+    );
+}
+
+#[test]
+fn zero_or_absurd_k_is_rejected() {
+    for k in ["0", "100000"] {
+        let dump = DUMP.replacen("k=15", &format!("k={k}"), 1);
+        let (reads, errors) = parse_file(dump.as_bytes(), None, false, &ReadFilter::default());
+        assert_eq!(reads.len(), 1);
+        let (block, error) = &errors[0];
+        assert_eq!(*block, 0);
+        assert_eq!(error.field, "k");
+        assert!(
+            error
+                .to_string()
+                .ends_with(": k must be between 1 and 1024")
+        );
+    }
+}
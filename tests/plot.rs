@@ -0,0 +1,413 @@
+use extract_chains::{
+    Anchor, Chain, Read,
+    palette::ColorPalette,
+    plot::{
+        Axes, ImageFormat, PlotOptions, ReadDirs, anchor_links, anchor_segment, chain_legend,
+        chain_path, coverage_bins, diff_panels, drawn_ref_extent, highlight_points, plot_chain,
+        plot_contact_sheet, plot_diff, render_chain_rgba, rich_title, scale_bar_label,
+        scale_bar_length,
+    },
+};
+use plotters::style::RGBColor;
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+fn revcomp_read() -> Read {
+    let anchors = vec![
+        Anchor {
+            ref_start: 1000,
+            query_start: 0,
+        },
+        Anchor {
+            ref_start: 1040,
+            query_start: 40,
+        },
+    ];
+    Read {
+        name: "rev".to_owned(),
+        read_len: 100,
+        k: 20,
+        fwd_anchors: Vec::new(),
+        rev_anchors: anchors.clone(),
+        chains: vec![Chain {
+            ref_id: 0,
+            score: 40.0,
+            mapq: None,
+            qspan: [0, 60],
+            rspan: [1000, 1060],
+            is_revcomp: true,
+            anchors,
+            cigar: "100M".to_owned(),
+            ref_start: 1000,
+            considered: true,
+            ssw_cigar: "100M".to_owned(),
+            ssw_ref_start: 1000,
+        }],
+        cigar_count: None,
+    }
+}
+
+fn options() -> PlotOptions {
+    PlotOptions {
+        mapping_only: true,
+        format: ImageFormat::Svg,
+        size: (200, 200),
+        highlight_tolerance: 0,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn revcomp_anchors_slope_downwards() {
+    let read = revcomp_read();
+    let chain = &read.chains[0];
+    for anchor in &chain.anchors {
+        let [(r0, q0), (r1, q1)] = anchor_segment(&read, chain.is_revcomp, anchor);
+        assert!(r1 > r0);
+        assert!(q1 < q0);
+    }
+    assert_eq!(
+        anchor_segment(&read, chain.is_revcomp, &chain.anchors[0]),
+        [(1000, 100), (1020, 80)]
+    );
+}
+
+#[test]
+fn revcomp_cigar_path_slopes_downwards() {
+    let read = revcomp_read();
+    let chain = &read.chains[0];
+    assert_eq!(
+        chain_path(&read, chain, &chain.cigar, chain.ref_start),
+        vec![(1000, 100), (1100, 0)]
+    );
+}
+
+#[test]
+fn forward_anchors_are_unchanged() {
+    let mut read = revcomp_read();
+    read.chains[0].is_revcomp = false;
+    let chain = &read.chains[0];
+    assert_eq!(
+        anchor_segment(&read, chain.is_revcomp, &chain.anchors[1]),
+        [(1040, 40), (1060, 60)]
+    );
+}
+
+#[test]
+fn anchors_near_the_end_of_the_coordinate_range_are_cut_short() {
+    let read = revcomp_read();
+    let anchor = Anchor {
+        ref_start: u32::MAX - 5,
+        query_start: u32::MAX - 10,
+    };
+    assert_eq!(
+        anchor_segment(&read, false, &anchor),
+        [(u32::MAX - 5, u32::MAX - 10), (u32::MAX, u32::MAX)]
+    );
+}
+
+#[test]
+fn out_of_order_anchors_are_linked_as_jumps() {
+    let mut read = revcomp_read();
+    read.chains[0].is_revcomp = false;
+    read.chains[0].anchors.push(Anchor {
+        ref_start: 1010,
+        query_start: 70,
+    });
+    let links = anchor_links(&read, &read.chains[0]);
+
+    assert_eq!(
+        links,
+        vec![
+            ([(1020, 20), (1040, 40)], true),
+            ([(1060, 60), (1010, 70)], false),
+        ]
+    );
+}
+
+#[test]
+fn inverted_reference_span_is_skipped() {
+    let mut read = revcomp_read();
+    read.name = "inverted".to_owned();
+    read.chains[0].rspan = [1060, 1000];
+    let options = options();
+    let read_dir = TestDir::new("inverted_span");
+    let warnings = captured_warnings();
+
+    assert_eq!(
+        plot_chain(&read, &read.chains[0], 0, &read_dir.0, &options),
+        None
+    );
+    assert!(!read_dir.0.exists());
+    assert!(warnings.lock().unwrap().contains(
+        &"inverted: chain 0 has inverted reference span 1060..1000, skipping".to_owned()
+    ));
+}
+
+#[test]
+fn read_dirs_keep_dots_and_never_collide() {
+    let mut dirs = ReadDirs::default();
+    assert_eq!(dirs.dir_for("read.1"), "read.1");
+    assert_eq!(dirs.dir_for("read_1"), "read_1");
+    assert_eq!(dirs.dir_for("read 1"), "read_1_2");
+    assert_eq!(dirs.dir_for("read/1"), "read_1_3");
+    assert_eq!(dirs.dir_for(".."), "_.");
+}
+
+#[test]
+fn scale_bar_uses_round_lengths() {
+    assert_eq!(scale_bar_length(200), 20);
+    assert_eq!(scale_bar_length(1_000), 200);
+    assert_eq!(scale_bar_length(30_000), 5_000);
+    assert_eq!(scale_bar_length(3), 1);
+    assert_eq!(scale_bar_label(500), "500 bp");
+    assert_eq!(scale_bar_label(5_000), "5 kb");
+    assert_eq!(scale_bar_label(2_000_000), "2 Mb");
+}
+
+#[test]
+fn chains_render_into_memory() {
+    let read = revcomp_read();
+    let image = render_chain_rgba(&read, 0, &options()).unwrap();
+
+    assert_eq!((image.width, image.height), (200, 200));
+    assert_eq!(image.pixels.len(), 200 * 200 * 4);
+    assert_eq!(image.pixels[..4], [255, 255, 255, 255]);
+    assert!(
+        image
+            .pixels
+            .chunks_exact(4)
+            .any(|pixel| pixel[..3] != [255, 255, 255])
+    );
+}
+
+#[test]
+fn swapped_axes_transpose_the_plot() {
+    assert_eq!(Axes::RefQuery.point((1000, 40)), (1000, 40));
+    assert_eq!(Axes::QueryRef.point((1000, 40)), (40, 1000));
+    assert_eq!(
+        Axes::RefQuery.ranges(1000..1100, 0..100),
+        (1000..1100, 0..100)
+    );
+    assert_eq!(
+        Axes::QueryRef.ranges(1000..1100, 0..100),
+        (0..100, 1000..1100)
+    );
+}
+
+#[test]
+fn chain_legend_shows_score_and_strand() {
+    let mut chain = revcomp_read().chains.remove(0);
+    chain.score = 842.34;
+    assert_eq!(chain_legend(&chain), "score=842.3 (-strand, considered)");
+
+    chain.is_revcomp = false;
+    chain.considered = false;
+    assert_eq!(
+        chain_legend(&chain),
+        "score=842.3 (+strand, not considered)"
+    );
+}
+
+#[test]
+fn coverage_track_counts_anchor_starts_per_bin() {
+    let read = revcomp_read();
+    let anchors = read.rev_anchors.iter().chain(&read.chains[0].anchors);
+
+    let (bin_width, counts) = coverage_bins(anchors, 980..1180);
+    assert_eq!(bin_width, 2);
+    assert_eq!(counts.len(), 100);
+    assert_eq!(counts[10], 2);
+    assert_eq!(counts[30], 2);
+    assert_eq!(counts.iter().sum::<u32>(), 4);
+
+    let (bin_width, counts) = coverage_bins(&read.rev_anchors, 1000..1010);
+    assert_eq!(
+        (bin_width, counts),
+        (1, [1, 0, 0, 0, 0, 0, 0, 0, 0, 0].to_vec())
+    );
+}
+
+#[test]
+fn highlighted_anchor_is_found_within_the_tolerance() {
+    let mut read = revcomp_read();
+    read.rev_anchors.push(Anchor {
+        ref_start: 3000,
+        query_start: 70,
+    });
+    let near = |ref_start, query_start| Anchor {
+        ref_start,
+        query_start,
+    };
+    assert_eq!(read.find_anchor(&near(1042, 38), 2), Some(vec![0]));
+    assert_eq!(read.find_anchor(&near(3001, 70), 2), Some(vec![]));
+    assert_eq!(read.find_anchor(&near(1043, 40), 2), None);
+
+    let highlight = |target| PlotOptions {
+        highlight_anchor: Some(target),
+        highlight_tolerance: 2,
+        ..options()
+    };
+    let points = |options: &PlotOptions| {
+        highlight_points(&read, true, read.chains[0].anchors.iter(), options)
+    };
+    assert!(points(&options()).is_empty());
+    assert!(points(&highlight(near(1043, 40))).is_empty());
+    assert_eq!(
+        points(&highlight(near(1042, 38))),
+        HashSet::from([(1040, 60)])
+    );
+    let swapped = PlotOptions {
+        axes: Axes::QueryRef,
+        ..highlight(near(1042, 38))
+    };
+    assert_eq!(points(&swapped), HashSet::from([(60, 1040)]));
+}
+
+#[test]
+fn rich_caption_falls_back_without_an_identity() {
+    let mut read = revcomp_read();
+    read.chains[0].cigar = "50=2X8=".to_owned();
+    assert_eq!(
+        rich_title(&read.chains[0], &read, &options()),
+        "Score: 40.00, Ref: 0, Span: 1000-1060 (-)\nIdentity: 96.67%, Query coverage: 60.00%"
+    );
+
+    read.chains[0].cigar.clear();
+    assert_eq!(
+        rich_title(&read.chains[0], &read, &options()),
+        "Score: 40.00, Ref: 0, Span: 1000-1060 (-)\nIdentity: n/a, Query coverage: 60.00%"
+    );
+    let rich = PlotOptions {
+        rich_caption: true,
+        ..options()
+    };
+    let captioned = render_chain_rgba(&read, 0, &rich).unwrap();
+    assert_eq!((captioned.width, captioned.height), (200, 200));
+}
+
+#[test]
+fn contact_sheet_grid_is_sized_from_the_chain_count() {
+    let mut read = revcomp_read();
+    read.name = "sheet/read".to_owned();
+    let chain = read.chains[0].clone();
+    read.chains.push(chain.clone());
+    read.chains.push(chain);
+    let read_dir = TestDir::new("contact_sheet");
+    std::fs::create_dir_all(&read_dir.0).unwrap();
+
+    let path = plot_contact_sheet(&read, &read_dir.0, &options()).unwrap();
+    assert_eq!(path, read_dir.0.join("sheet_read_contactsheet.png"));
+    let (width, height) = image_size(&path);
+    assert_eq!((width, height), (800, 600));
+}
+
+#[test]
+fn ref_extent_covers_only_what_is_drawn() {
+    let mut read = revcomp_read();
+    read.chains[0].cigar = "30M".to_owned();
+    read.chains[0].ssw_ref_start = 1050;
+    read.chains[0].ssw_cigar = "20M".to_owned();
+    let chain = &read.chains[0];
+    let mut options = options();
+
+    assert_eq!(drawn_ref_extent(&read, chain, &options), (1000, 1060));
+    options.mapping_only = false;
+    assert_eq!(drawn_ref_extent(&read, chain, &options), (1000, 1070));
+}
+
+#[test]
+fn diff_plots_cover_reads_of_either_input() {
+    let shared = revcomp_read();
+    let mut only_a = revcomp_read();
+    only_a.name = "only_a".to_owned();
+    let mut unconsidered = revcomp_read();
+    unconsidered.name = "unconsidered".to_owned();
+    unconsidered.chains[0].considered = false;
+    let a = [shared, only_a, unconsidered];
+    let b = [revcomp_read()];
+    let output = TestDir::new("diff");
+    let output = output.0.to_str().unwrap();
+
+    let written = plot_diff([&a, &b], ["a", "b"], output, &options());
+    assert_eq!(
+        written,
+        ["rev/diff.svg", "only_a/diff.svg"].map(|file| Path::new(output).join(file))
+    );
+    assert!(written.iter().all(|path| path.exists()));
+}
+
+#[test]
+fn series_colours_follow_the_palette() {
+    let okabe_ito = ColorPalette::Colorblind.colors();
+    assert_eq!(okabe_ito.series(0), RGBColor(0x00, 0x72, 0xB2));
+    assert_eq!(okabe_ito.series(7), okabe_ito.series(0));
+    let grey = ColorPalette::Grayscale.colors().series(1);
+    assert!(grey.0 == grey.1 && grey.1 == grey.2);
+}
+
+#[test]
+fn diff_chains_on_different_references_get_a_panel_each() {
+    let a = revcomp_read();
+    let mut b = revcomp_read();
+    assert_eq!(diff_panels([Some(&a), Some(&b)]), [vec![0, 1]]);
+    assert_eq!(diff_panels([Some(&a), None]), [vec![0, 1]]);
+
+    b.chains[0].ref_id = 1;
+    assert_eq!(diff_panels([Some(&a), Some(&b)]), [vec![0], vec![1]]);
+}
+
+/// Starts capturing warnings, returning those logged by any test in this
+/// binary from then on.
+fn captured_warnings() -> &'static Mutex<Vec<String>> {
+    static LOGGER: CapturingLogger = CapturingLogger(Mutex::new(Vec::new()));
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(log::LevelFilter::Warn);
+    }
+    &LOGGER.0
+}
+
+struct CapturingLogger(Mutex<Vec<String>>);
+
+impl log::Log for CapturingLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::Level::Warn
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            self.0.lock().unwrap().push(record.args().to_string());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// A directory under the system temp directory unique to one test of one
+/// process, so parallel runs do not share output, removed when dropped.
+struct TestDir(PathBuf);
+
+impl TestDir {
+    fn new(test: &str) -> TestDir {
+        let dir =
+            std::env::temp_dir().join(format!("extract_chains_{test}_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        TestDir(dir)
+    }
+}
+
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Width and height from a PNG's IHDR chunk.
+fn image_size(path: &Path) -> (u32, u32) {
+    let bytes = std::fs::read(path).unwrap();
+    let be = |at: usize| u32::from_be_bytes(bytes[at..at + 4].try_into().unwrap());
+    (be(16), be(20))
+}
@@ -0,0 +1,48 @@
+use extract_chains::{
+    Anchor,
+    parse::parse_reads_iter,
+    stats::{ScoreCheck, chaining_score},
+};
+
+fn anchors(coords: &[(u32, u32)]) -> Vec<Anchor> {
+    coords
+        .iter()
+        .map(|&(ref_start, query_start)| Anchor {
+            ref_start,
+            query_start,
+        })
+        .collect()
+}
+
+#[test]
+fn chaining_score_adds_matched_bases_and_subtracts_gaps() {
+    assert_eq!(chaining_score(&[], 15), 0.0);
+    // Overlapping, then spaced, then off-diagonal by 5 bases.
+    let chain = anchors(&[(1000, 0), (1010, 10), (1040, 40), (1065, 60)]);
+    assert_eq!(
+        chaining_score(&chain, 15),
+        15.0 + 10.0 + 15.0 + (15.0 - 5.0)
+    );
+}
+
+#[test]
+fn reported_scores_are_compared_with_recomputed_ones() {
+    let dump = "\
+Query: read1
+L=100,k=15
+Anchors for forward strand [{1000,0}{1020,20}]
+Anchors for reverse strand []
+Chains[{ref_id=0,score=30,query_start=0,query_end=35,ref_start=1000,ref_end=1035,is_revcomp=false,anchors=[{1000,0}{1020,20}]}{ref_id=0,score=60,query_start=0,query_end=35,ref_start=1000,ref_end=1035,is_revcomp=false,anchors=[{1000,0}{1020,20}]}]
+Cigars:[(35M,was_considered=1,rstart=1000,ssw=35M,ssw_rstart=1000)(35M,was_considered=0,rstart=1000,ssw=35M,ssw_rstart=1000)]
+Done!
+";
+    let reads: Vec<_> = parse_reads_iter(dump.as_bytes())
+        .map(Result::unwrap)
+        .collect();
+    let check = ScoreCheck::compute(&reads).unwrap();
+
+    assert_eq!(check.chains, 2);
+    assert_eq!(check.min_ratio, 1.0);
+    assert_eq!(check.max_ratio, 2.0);
+    assert_eq!(check.mismatched, 1);
+}
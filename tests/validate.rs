@@ -0,0 +1,136 @@
+use extract_chains::{
+    parse::parse_reads_iter,
+    refs::RefNames,
+    validate::{check_all, check_cigar_count, check_read, check_ref_lengths},
+};
+
+const DUMP: &str = "\
+Query: read1
+L=30,k=15
+Anchors for forward strand [{1000,0}]
+Anchors for reverse strand [{2000,0}]
+Chains[{ref_id=0,score=15,query_start=0,query_end=15,ref_start=1000,ref_end=1015,is_revcomp=true,anchors=[{1000,0}]}]
+Cigars:[(30M,was_considered=1,rstart=1000,ssw=30M,ssw_rstart=1000)]
+Done!
+";
+
+#[test]
+fn chain_anchors_missing_from_their_strand_are_reported() {
+    let read = parse_reads_iter(DUMP.as_bytes()).next().unwrap().unwrap();
+    let warnings = check_read(&read);
+
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].chain, Some(0));
+    assert_eq!(
+        warnings[0].message,
+        "1 of 1 anchors are not among the reverse-strand anchors"
+    );
+}
+
+#[test]
+fn anchors_past_the_read_end_are_reported_and_clamped() {
+    let dump = DUMP.replace(
+        "Anchors for forward strand [{1000,0}]",
+        "Anchors for forward strand [{1000,0},{1020,20}]",
+    );
+    let mut read = parse_reads_iter(dump.as_bytes()).next().unwrap().unwrap();
+    let warnings = check_read(&read);
+
+    assert_eq!(warnings[0].chain, None);
+    assert_eq!(
+        warnings[0].message,
+        "1 of 3 anchors run past the end of the read (query_start + k > 30)"
+    );
+
+    assert_eq!(read.clamp_anchors(), 1);
+    assert_eq!(read.fwd_anchors[1].query_start, 15);
+    assert!(
+        check_read(&read)
+            .iter()
+            .all(|warning| warning.chain.is_some())
+    );
+}
+
+#[test]
+fn spans_past_the_declared_reference_length_are_reported() {
+    let read = parse_reads_iter(DUMP.as_bytes()).next().unwrap().unwrap();
+    let mut ref_names = RefNames::parse("0\tchr1\n").unwrap();
+    assert!(check_ref_lengths(&read, &ref_names).is_empty());
+
+    ref_names.parse_lengths("# id\tlength\n0\t1010\n").unwrap();
+    let warnings = check_ref_lengths(&read, &ref_names);
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].chain, Some(0));
+    assert_eq!(
+        warnings[0].message,
+        "reference span ends at 1015, past the 1010 bases of reference chr1"
+    );
+
+    assert!(ref_names.parse_lengths("0\tlong\n").is_err());
+}
+
+#[test]
+fn query_spans_outside_the_read_are_reported() {
+    let dump = DUMP.replace("query_start=0,query_end=15", "query_start=20,query_end=35");
+    let read = parse_reads_iter(dump.as_bytes()).next().unwrap().unwrap();
+    let messages: Vec<_> = check_read(&read)
+        .into_iter()
+        .map(|warning| warning.message)
+        .collect();
+    assert!(messages.contains(&"query span 20-35 ends past the read length 30".to_owned()));
+
+    let dump = DUMP.replace("query_start=0,query_end=15", "query_start=15,query_end=15");
+    let read = parse_reads_iter(dump.as_bytes()).next().unwrap().unwrap();
+    assert_eq!(
+        check_read(&read)[0].to_string(),
+        "read read1 chain 0: query span 15-15 is empty or inverted"
+    );
+}
+
+#[test]
+fn check_all_gathers_inverted_reference_spans_and_missing_cigars() {
+    let dump = DUMP
+        .replace("ref_start=1000,ref_end=1015", "ref_start=1015,ref_end=1000")
+        .replace(
+            "Cigars:[(30M,was_considered=1,rstart=1000,ssw=30M,ssw_rstart=1000)]",
+            "Cigars:[]",
+        );
+    let read = parse_reads_iter(dump.as_bytes()).next().unwrap().unwrap();
+    let ref_names = RefNames::default();
+
+    let messages = |cigar_count| -> Vec<String> {
+        check_all(&read, &ref_names, cigar_count)
+            .into_iter()
+            .map(|warning| warning.message)
+            .collect()
+    };
+    assert!(messages(false).contains(&"reference span 1015-1000 is empty or inverted".to_owned()));
+    assert!(
+        !messages(false)
+            .iter()
+            .any(|message| message.contains("no CIGAR"))
+    );
+    assert!(
+        messages(true)
+            .contains(&"0 CIGARs for 1 chains; the last 1 chains have no CIGAR".to_owned())
+    );
+}
+
+#[test]
+fn extra_cigars_are_a_warning_rather_than_a_parse_error() {
+    let dump = DUMP.replace(
+        "ssw_rstart=1000)]",
+        "ssw_rstart=1000)(20M,was_considered=0,rstart=1500,ssw=20M,ssw_rstart=1500)]",
+    );
+    let read = parse_reads_iter(dump.as_bytes()).next().unwrap().unwrap();
+
+    assert_eq!(read.cigar_count, Some(2));
+    assert_eq!(read.chains[0].cigar, "30M");
+    assert_eq!(
+        check_cigar_count(&read).unwrap().to_string(),
+        "read read1: 2 CIGARs for 1 chains; the last 1 CIGARs are ignored"
+    );
+    assert!(
+        check_cigar_count(&parse_reads_iter(DUMP.as_bytes()).next().unwrap().unwrap()).is_none()
+    );
+}